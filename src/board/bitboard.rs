@@ -0,0 +1,210 @@
+use super::{magic, pieces::Color, square::Square};
+
+// A set of squares, the bit with the number of Square::index is set for every square in the set.
+// a8 is the lowest bit and h1 the highest, the same order as Board::pieces
+pub type Bitboard = u64;
+
+// The set with only the square
+pub fn square_set(square: Square) -> Bitboard {
+    return 1 << square.index();
+}
+
+pub fn contains(set: Bitboard, square: Square) -> bool {
+    return set & square_set(square) != 0;
+}
+
+// The squares in the set, from a8 to h1
+pub fn squares(set: Bitboard) -> impl Iterator<Item = Square> {
+    let mut remaining = set;
+    return std::iter::from_fn(move || {
+        if remaining == 0 {
+            return None;
+        }
+        let index = remaining.trailing_zeros() as usize;
+        // Clears the lowest bit
+        remaining &= remaining - 1;
+        return Square::from_index(index);
+    });
+}
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (-2, -1),
+    (-2, 1),
+    (2, -1),
+    (2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+];
+const KING_OFFSETS: [(i32, i32); 8] = [
+    (0, 1),
+    (0, -1),
+    (1, 0),
+    (-1, 0),
+    (1, -1),
+    (1, 1),
+    (-1, -1),
+    (-1, 1),
+];
+// White pawns capture up the board and black pawns down
+const WHITE_PAWN_OFFSETS: [(i32, i32); 2] = [(-1, -1), (1, -1)];
+const BLACK_PAWN_OFFSETS: [(i32, i32); 2] = [(-1, 1), (1, 1)];
+
+// For every square the squares one jump away, used for the pieces that do not slide
+const fn jump_table(offsets: &[(i32, i32)]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut index = 0;
+    while index < 64 {
+        let x = (index % 8) as i32;
+        let y = (index / 8) as i32;
+        let mut i = 0;
+        while i < offsets.len() {
+            let to_x = x + offsets[i].0;
+            let to_y = y + offsets[i].1;
+            if 0 <= to_x && to_x < 8 && 0 <= to_y && to_y < 8 {
+                table[index] |= 1 << (to_y * 8 + to_x);
+            }
+            i += 1;
+        }
+        index += 1;
+    }
+    return table;
+}
+
+const KNIGHT_ATTACKS: [Bitboard; 64] = jump_table(&KNIGHT_OFFSETS);
+const KING_ATTACKS: [Bitboard; 64] = jump_table(&KING_OFFSETS);
+const WHITE_PAWN_ATTACKS: [Bitboard; 64] = jump_table(&WHITE_PAWN_OFFSETS);
+const BLACK_PAWN_ATTACKS: [Bitboard; 64] = jump_table(&BLACK_PAWN_OFFSETS);
+
+pub fn knight_attacks(square: Square) -> Bitboard {
+    return KNIGHT_ATTACKS[square.index()];
+}
+
+pub fn king_attacks(square: Square) -> Bitboard {
+    return KING_ATTACKS[square.index()];
+}
+
+// The squares a pawn of the color on the square captures on
+pub fn pawn_attacks(square: Square, color: Color) -> Bitboard {
+    match color {
+        Color::WHITE => WHITE_PAWN_ATTACKS[square.index()],
+        Color::BLACK => BLACK_PAWN_ATTACKS[square.index()],
+        Color::EMPTY => 0,
+    }
+}
+
+// occupied is every square with a piece on it, the attacks stop at the first piece in each direction
+// Looked up in the magic bitboard tables
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    return magic::rook_attacks(square, occupied);
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    return magic::bishop_attacks(square, occupied);
+}
+
+pub fn queen_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    return rook_attacks(square, occupied) | bishop_attacks(square, occupied);
+}
+
+// The step from a towards b if they are on the same row, file or diagonal
+fn direction(a: Square, b: Square) -> Option<(i32, i32)> {
+    let (dx, dy) = (b.x() - a.x(), b.y() - a.y());
+    if a == b || !(dx == 0 || dy == 0 || dx.abs() == dy.abs()) {
+        return None;
+    }
+    return Some((dx.signum(), dy.signum()));
+}
+
+// The squares strictly between a and b, empty if they are not on the same line
+pub fn between(a: Square, b: Square) -> Bitboard {
+    let Some((dx, dy)) = direction(a, b) else {
+        return 0;
+    };
+    let mut set = 0;
+    let mut current = a;
+    while let Some(next) = current.offset(dx, dy) {
+        if next == b {
+            break;
+        }
+        set |= square_set(next);
+        current = next;
+    }
+    return set;
+}
+
+// Every square on the line through a and b from edge to edge, empty if they are not on the same line
+pub fn line(a: Square, b: Square) -> Bitboard {
+    let Some((dx, dy)) = direction(a, b) else {
+        return 0;
+    };
+    let mut set = square_set(a);
+    for (step_x, step_y) in [(dx, dy), (-dx, -dy)] {
+        let mut current = a;
+        while let Some(next) = current.offset(step_x, step_y) {
+            set |= square_set(next);
+            current = next;
+        }
+    }
+    return set;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sq(name: &str) -> Square {
+        return name.parse().unwrap();
+    }
+
+    const FILE_D: Bitboard = 0x0808_0808_0808_0808;
+
+    fn set(names: &[&str]) -> Bitboard {
+        return names.iter().fold(0, |set, name| set | square_set(sq(name)));
+    }
+
+    #[test]
+    fn jump_attacks() {
+        assert_eq!(knight_attacks(sq("a8")), set(&["b6", "c7"]));
+        assert_eq!(knight_attacks(sq("e4")).count_ones(), 8);
+        assert_eq!(king_attacks(sq("h1")), set(&["g1", "g2", "h2"]));
+        assert_eq!(pawn_attacks(sq("e2"), Color::WHITE), set(&["d3", "f3"]));
+        assert_eq!(pawn_attacks(sq("a7"), Color::BLACK), set(&["b6"]));
+        assert_eq!(pawn_attacks(sq("e2"), Color::EMPTY), 0);
+    }
+
+    #[test]
+    fn sliding_attacks_stop_at_pieces() {
+        let occupied = set(&["d6", "d2", "b4", "g4"]);
+        assert_eq!(
+            rook_attacks(sq("d4"), occupied),
+            set(&["d5", "d6", "d3", "d2", "c4", "b4", "e4", "f4", "g4"])
+        );
+        assert_eq!(bishop_attacks(sq("a1"), 0).count_ones(), 7);
+        assert_eq!(bishop_attacks(sq("a1"), set(&["c3"])), set(&["b2", "c3"]));
+        assert_eq!(queen_attacks(sq("d4"), 0).count_ones(), 27);
+    }
+
+    #[test]
+    fn lines_between_squares() {
+        assert_eq!(between(sq("e1"), sq("e4")), set(&["e2", "e3"]));
+        assert_eq!(between(sq("h8"), sq("e5")), set(&["g7", "f6"]));
+        assert_eq!(between(sq("e1"), sq("e2")), 0);
+        assert_eq!(between(sq("e1"), sq("f3")), 0);
+        assert_eq!(line(sq("b2"), sq("c3")).count_ones(), 8);
+        assert!(contains(line(sq("b2"), sq("c3")), sq("h8")));
+        assert_eq!(line(sq("d4"), sq("d6")), FILE_D);
+        assert_eq!(line(sq("e1"), sq("f3")), 0);
+    }
+
+    #[test]
+    fn iterate_squares() {
+        let squares: Vec<String> = squares(set(&["h1", "a8", "e4"]))
+            .map(|square| square.to_string())
+            .collect();
+        assert_eq!(squares, vec!["a8", "e4", "h1"]);
+        assert!(contains(set(&["c2"]), sq("c2")));
+        assert!(!contains(set(&["c2"]), sq("g5")));
+    }
+}
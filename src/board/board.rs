@@ -1,18 +1,76 @@
-use std::any::Any;
+use std::{
+    hash::{Hash, Hasher},
+    ops::Index,
+};
 
-use crate::{game::Game, utils::matrix::index_to_col_row};
+use crate::game::Game;
 
 use super::{
-    parser::parse_fen_string,
-    pieces::{
-        get_legal_moves, get_pseudo_legal_moves, possible_moves_for_color, Color, Move, Piece,
-        PieceType,
+    bitboard::{
+        between, bishop_attacks, king_attacks, knight_attacks, pawn_attacks, rook_attacks,
+        square_set, squares, Bitboard,
     },
+    parser::{parse_fen_string, serialize_board_fen},
+    pieces::{possible_moves_for_color, Color, Piece, PieceType},
+    square::Square,
+    zobrist::piece_key,
 };
 
-#[derive(Clone, Copy)]
+// For every square the pieces of each side that attack it. A piece attacks the squares it
+// could capture on, so a piece of the same side on an attacked square is defended
+#[derive(Clone, Copy, Debug)]
+pub struct AttackMap {
+    // Indexed by Square::index
+    pub white: [Bitboard; 64],
+    pub black: [Bitboard; 64],
+}
+
+impl AttackMap {
+    // The pieces of the color that attack the square
+    pub fn attackers(&self, square: Square, color: Color) -> Bitboard {
+        return match color {
+            Color::WHITE => self.white[square.index()],
+            Color::BLACK => self.black[square.index()],
+            Color::EMPTY => 0,
+        };
+    }
+
+    pub fn count(&self, square: Square, color: Color) -> u32 {
+        return self.attackers(square, color).count_ones();
+    }
+
+    // Every square at least one piece of the color attacks
+    pub fn attacked_squares(&self, color: Color) -> Bitboard {
+        return Square::all()
+            .filter(|square| self.attackers(*square, color) != 0)
+            .fold(0, |set, square| set | square_set(square));
+    }
+
+    // The pieces of the color, other than the king, that the other side attacks and that no piece defends
+    pub fn hanging(&self, board: &Board, color: Color) -> Bitboard {
+        let pieces = board.occupancy(color) & !board.pieces_of(color, PieceType::KING);
+        return squares(pieces)
+            .filter(|square| {
+                self.attackers(*square, color.opposite()) != 0
+                    && self.attackers(*square, color) == 0
+            })
+            .fold(0, |set, square| set | square_set(square));
+    }
+}
+
+// The pieces are kept twice: as a matrix to look up the piece on a square
+// and as bitboards to find pieces and attacks quickly.
+// Both are private and only changed through set_piece, so they always stay the same
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Board {
-    pub pieces: [[Piece; 8]; 8],
+    // pieces[y][x], with y the row from the top
+    pieces: [[Piece; 8]; 8],
+    // The squares of every piece type, indexed by color_index and piece_index
+    bitboards: [[Bitboard; 6]; 2],
+    // The squares with a piece of the color, indexed by color_index
+    occupancy: [Bitboard; 2],
+    // Zobrist key of the pieces, updated by set_piece
+    key: u64,
 }
 
 impl Board {
@@ -46,12 +104,12 @@ impl Board {
                 },
                 Piece {
                     color: Color::BLACK,
-                    piece_type: PieceType::KNIGHT,
+                    piece_type: PieceType::BISHOP,
                     has_moved: false,
                 },
                 Piece {
                     color: Color::BLACK,
-                    piece_type: PieceType::BISHOP,
+                    piece_type: PieceType::KNIGHT,
                     has_moved: false,
                 },
                 Piece {
@@ -90,12 +148,12 @@ impl Board {
                 },
                 Piece {
                     color: Color::WHITE,
-                    piece_type: PieceType::KNIGHT,
+                    piece_type: PieceType::BISHOP,
                     has_moved: false,
                 },
                 Piece {
                     color: Color::WHITE,
-                    piece_type: PieceType::BISHOP,
+                    piece_type: PieceType::KNIGHT,
                     has_moved: false,
                 },
                 Piece {
@@ -117,10 +175,192 @@ impl Board {
             pieces[1] = black_pawns;
             pieces[0] = black_pieces;
 
-            let board = Board { pieces: pieces };
+            let board = Board::from_pieces(pieces);
             return board;
         }
-        return parse_fen_string(fen.unwrap());
+        // Use parse_fen_string directly to handle invalid fen strings
+        return parse_fen_string(fen.unwrap()).expect("Invalid fen string");
+    }
+
+    // Builds the board and its bitboards from the matrix of pieces
+    pub fn from_pieces(pieces: [[Piece; 8]; 8]) -> Board {
+        let mut board = Board::empty();
+        for square in Square::all() {
+            board.set_piece(square, pieces[square.y() as usize][square.x() as usize]);
+        }
+        return board;
+    }
+
+    // A board without any pieces
+    pub fn empty() -> Board {
+        return Board {
+            pieces: [[Piece {
+                color: Color::EMPTY,
+                piece_type: PieceType::EMPTY,
+                has_moved: false,
+            }; 8]; 8],
+            bitboards: [[0; 6]; 2],
+            occupancy: [0; 2],
+            key: 0,
+        };
+    }
+
+    // The piece on the square, the same as board[square]
+    pub fn piece_at(&self, square: Square) -> Piece {
+        return self[square];
+    }
+
+    // A copy of all the pieces as pieces[y][x], with y the row from the top
+    pub fn pieces(&self) -> [[Piece; 8]; 8] {
+        return self.pieces;
+    }
+
+    // The piece placement field of a fen string
+    pub fn to_fen(&self) -> String {
+        return serialize_board_fen(self);
+    }
+
+    // Puts the piece on the square, replacing what stood there. Use an empty piece to clear the square
+    pub fn set_piece(&mut self, square: Square, piece: Piece) {
+        let old = self[square];
+        let bit = square_set(square);
+        if let (Some(color), Some(piece_type)) =
+            (color_index(old.color), piece_index(old.piece_type))
+        {
+            self.bitboards[color][piece_type] &= !bit;
+            self.occupancy[color] &= !bit;
+            self.key ^= piece_key(color, piece_type, square);
+        }
+        if let (Some(color), Some(piece_type)) =
+            (color_index(piece.color), piece_index(piece.piece_type))
+        {
+            self.bitboards[color][piece_type] |= bit;
+            self.occupancy[color] |= bit;
+            self.key ^= piece_key(color, piece_type, square);
+        }
+        self.pieces[square.y() as usize][square.x() as usize] = piece;
+    }
+
+    // 64 bit Zobrist key of the piece placement, the same placement always has the same key.
+    // Game::zobrist_key adds the side to move, castling rights and en passant
+    pub fn zobrist_key(&self) -> u64 {
+        return self.key;
+    }
+
+    // The squares of the pieces of the color and type
+    pub fn pieces_of(&self, color: Color, piece_type: PieceType) -> Bitboard {
+        return match (color_index(color), piece_index(piece_type)) {
+            (Some(color), Some(piece_type)) => self.bitboards[color][piece_type],
+            _ => 0,
+        };
+    }
+
+    // The squares with a piece of the color
+    pub fn occupancy(&self, color: Color) -> Bitboard {
+        return match color_index(color) {
+            Some(color) => self.occupancy[color],
+            None => 0,
+        };
+    }
+
+    // The squares with a piece of any color
+    pub fn occupied(&self) -> Bitboard {
+        return self.occupancy[0] | self.occupancy[1];
+    }
+
+    pub fn king_square(&self, color: Color) -> Option<Square> {
+        return squares(self.pieces_of(color, PieceType::KING)).next();
+    }
+
+    // The pieces of the color that attack the square, a piece on the square itself is not counted
+    pub fn attackers(&self, square: Square, color: Color) -> Bitboard {
+        let occupied = self.occupied();
+        let queens = self.pieces_of(color, PieceType::QUEEN);
+        let rooks = self.pieces_of(color, PieceType::ROOK) | queens;
+        let bishops = self.pieces_of(color, PieceType::BISHOP) | queens;
+        // A pawn attacks the square if a pawn of the other side on the square would attack the pawn
+        return (pawn_attacks(square, color.opposite()) & self.pieces_of(color, PieceType::PAWN))
+            | (knight_attacks(square) & self.pieces_of(color, PieceType::KNIGHT))
+            | (king_attacks(square) & self.pieces_of(color, PieceType::KING))
+            | (rook_attacks(square, occupied) & rooks)
+            | (bishop_attacks(square, occupied) & bishops);
+    }
+
+    // Which pieces attack every square, for both sides
+    pub fn attack_map(&self) -> AttackMap {
+        let mut map = AttackMap {
+            white: [0; 64],
+            black: [0; 64],
+        };
+        for square in Square::all() {
+            map.white[square.index()] = self.attackers(square, Color::WHITE);
+            map.black[square.index()] = self.attackers(square, Color::BLACK);
+        }
+        return map;
+    }
+
+    // The pieces of the other side that give check to the king of color
+    pub fn checkers(&self, color: Color) -> Bitboard {
+        let Some(king) = self.king_square(color) else {
+            return 0;
+        };
+        return self.attackers(king, color.opposite());
+    }
+
+    // The pieces of color that can not leave the line between their king and an enemy rook,
+    // bishop or queen without putting the king in check
+    pub fn pinned(&self, color: Color) -> Bitboard {
+        let Some(king) = self.king_square(color) else {
+            return 0;
+        };
+        let enemy = color.opposite();
+        let queens = self.pieces_of(enemy, PieceType::QUEEN);
+        // The sliding pieces that would attack the king if nothing stood in between
+        let snipers = (rook_attacks(king, 0) & (self.pieces_of(enemy, PieceType::ROOK) | queens))
+            | (bishop_attacks(king, 0) & (self.pieces_of(enemy, PieceType::BISHOP) | queens));
+        let mut pinned = 0;
+        for sniper in squares(snipers) {
+            let blockers = between(king, sniper) & self.occupied();
+            if blockers.count_ones() == 1 {
+                pinned |= blockers & self.occupancy(color);
+            }
+        }
+        return pinned;
+    }
+}
+
+fn color_index(color: Color) -> Option<usize> {
+    return match color {
+        Color::WHITE => Some(0),
+        Color::BLACK => Some(1),
+        Color::EMPTY => None,
+    };
+}
+
+fn piece_index(piece_type: PieceType) -> Option<usize> {
+    return match piece_type {
+        PieceType::PAWN => Some(0),
+        PieceType::ROOK => Some(1),
+        PieceType::KNIGHT => Some(2),
+        PieceType::BISHOP => Some(3),
+        PieceType::KING => Some(4),
+        PieceType::QUEEN => Some(5),
+        PieceType::EMPTY => None,
+    };
+}
+
+// Boards that are equal have the same pieces and so the same key
+impl Hash for Board {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.key);
+    }
+}
+
+impl Index<Square> for Board {
+    type Output = Piece;
+
+    fn index(&self, square: Square) -> &Piece {
+        return &self.pieces[square.y() as usize][square.x() as usize];
     }
 }
 
@@ -134,7 +374,7 @@ fn fill_pawns(color: Color) -> [Piece; 8] {
     return pawns;
 }
 
-pub fn in_check_mate(game: &mut Game, color: Color) -> bool {
+pub fn in_check_mate(game: &Game, color: Color) -> bool {
     if color == Color::WHITE && game.white_in_check {
         // check all possible moves for white and if empty then check mate
         let possible_moves = possible_moves_for_color(game, color);
@@ -146,109 +386,123 @@ pub fn in_check_mate(game: &mut Game, color: Color) -> bool {
     return false;
 }
 
-// Define check as if the king stands on a position that can be reached through a legal move then we are in check
-// Can use this to remove moves that puts team in check
-// Check mate is when a team is in check and there no legal moves left
-pub fn in_check(board: Board, color: Color) -> bool {
-    // generate valid moves for all the pieces in the other team
-    let mut opposing_color = Color::EMPTY;
-    if color == Color::WHITE {
-        opposing_color = Color::BLACK;
-    } else if color == Color::BLACK {
-        opposing_color = Color::WHITE;
-    }
-    let opposing_pieces: Vec<(&Piece, usize)> = board
-        .pieces
-        .iter()
-        .flatten()
-        .enumerate()
-        .map(|(i, piece)| (piece, i))
-        .filter(|(piece, _index)| piece.color == opposing_color)
-        .collect();
-
-    let possible_moves: Vec<Move> = opposing_pieces
-        .iter()
-        .map(|(_piece, index)| {
-            let (row, col) = index_to_col_row(*index).unwrap();
-
-            let pseudo_moves = get_pseudo_legal_moves(board, col, row, opposing_color);
-
-            return pseudo_moves;
-        })
-        .flatten()
-        .collect();
-
-    let mut check = false;
-    // Go through all possible moves and check if any of the moves can get to the king
-    for (index, possible_move) in possible_moves.iter().enumerate() {
-        if board.pieces[possible_move.1 as usize][possible_move.0 as usize].color == color
-            && board.pieces[possible_move.1 as usize][possible_move.0 as usize].piece_type
-                == PieceType::KING
-        {
-            // in check and want to return something
-            check = true;
-        }
+// Stalemate is when the team is not in check but has no legal moves left
+pub fn in_stalemate(game: &Game, color: Color) -> bool {
+    let in_check = match color {
+        Color::WHITE => game.white_in_check,
+        Color::BLACK => game.black_in_check,
+        Color::EMPTY => return false,
+    };
+    if in_check {
+        return false;
     }
-
-    return check;
+    return possible_moves_for_color(game, color).is_empty();
 }
 
-// Little confusing name but basically we check what would happen if the king was in that position
-// basically only used for castling
-pub fn positions_in_check(board: Board, color: Color, positions: Vec<(i32, i32)>) -> bool {
-    let mut opposing_color = Color::EMPTY;
-    if color == Color::WHITE {
-        opposing_color = Color::BLACK;
-    } else if color == Color::BLACK {
-        opposing_color = Color::WHITE;
-    }
-    let opposing_pieces: Vec<(&Piece, usize)> = board
-        .pieces
-        .iter()
-        .flatten()
-        .enumerate()
-        .map(|(i, piece)| (piece, i))
-        .filter(|(piece, _index)| piece.color == opposing_color)
-        .collect();
-
-    let possible_moves: Vec<Move> = opposing_pieces
-        .iter()
-        .map(|(_piece, index)| {
-            let (row, col) = index_to_col_row(*index).unwrap();
-
-            let pseudo_moves = get_pseudo_legal_moves(board, col, row, opposing_color);
-            return pseudo_moves;
-        })
-        .flatten()
-        .collect();
-    let mut check = false;
-
-    for (index, possible_move) in possible_moves.iter().enumerate() {
-        if positions.contains(&(possible_move.0, possible_move.1)) {
-            check = true;
+// If color can not checkmate the other side with any sequence of legal moves,
+// used when the clock of the other side runs out
+pub fn insufficient_material_for(board: &Board, color: Color) -> bool {
+    let mut own_pieces: Vec<PieceType> = Vec::new();
+    let mut other_pieces: Vec<PieceType> = Vec::new();
+    let mut bishop_square_colors: Vec<i32> = Vec::new();
+    let mut has_knights = false;
+    let mut has_pawns = false;
+    for (y, row) in board.pieces.iter().enumerate() {
+        for (x, piece) in row.iter().enumerate() {
+            match piece.piece_type {
+                PieceType::EMPTY => continue,
+                PieceType::BISHOP => bishop_square_colors.push(((x + y) % 2) as i32),
+                PieceType::KNIGHT => has_knights = true,
+                PieceType::PAWN => has_pawns = true,
+                _ => {}
+            }
+            if piece.color == color {
+                own_pieces.push(piece.piece_type);
+            } else {
+                other_pieces.push(piece.piece_type);
+            }
         }
     }
 
-    return check;
+    if own_pieces.iter().any(|piece_type| {
+        matches!(
+            piece_type,
+            PieceType::PAWN | PieceType::ROOK | PieceType::QUEEN
+        )
+    }) {
+        return false;
+    }
+    if own_pieces.contains(&PieceType::KNIGHT) {
+        // A single knight can only mate if the other side has pieces that can block the king
+        return own_pieces.len() <= 2
+            && other_pieces
+                .iter()
+                .all(|piece_type| matches!(piece_type, PieceType::KING | PieceType::QUEEN));
+    }
+    if own_pieces.contains(&PieceType::BISHOP) {
+        // Bishops that all move on the same square color can never cover the squares around the king
+        let same_square_color = bishop_square_colors
+            .iter()
+            .all(|square_color| *square_color == bishop_square_colors[0]);
+        return same_square_color && !has_knights && !has_pawns;
+    }
+    // Only the king left
+    return true;
+}
+
+// Neither side can checkmate, for example king against king or king and bishop against king
+pub fn insufficient_material(board: &Board) -> bool {
+    return insufficient_material_for(board, Color::WHITE)
+        && insufficient_material_for(board, Color::BLACK);
+}
+
+// The side is in check if a piece of the other side attacks its king
+// Can use this to remove moves that puts team in check
+// Check mate is when a team is in check and there no legal moves left
+pub fn in_check(board: Board, color: Color) -> bool {
+    let Some(king) = board.king_square(color) else {
+        return false;
+    };
+    return board.attackers(king, color.opposite()) != 0;
 }
 
-pub fn board_from_fen() {}
+// Little confusing name but basically we check what would happen if the king was in that position
+// basically only used for castling
+pub fn positions_in_check(board: Board, color: Color, positions: Vec<Square>) -> bool {
+    return positions
+        .into_iter()
+        .any(|position| position_attacked(board, color, position));
+}
 
-// Set up the board with the right number of pieces and stuff
+// If the other side could capture a king of color standing on the square.
+// Pawns count the squares they capture on and not the ones they push to
+pub fn position_attacked(board: Board, color: Color, position: Square) -> bool {
+    return board.attackers(position, color.opposite()) != 0;
+}
 
 #[cfg(test)]
 mod tests {
 
-    use std::collections::HashMap;
-
     use crate::board::{
-        self,
+        bitboard::contains,
         parser::{self, parse_fen_string, print_row},
-        pieces::{can_pawn_promote, castle_possible, get_legal_moves, move_piece, Move},
+        pieces::{castle_possible, get_legal_moves, get_pseudo_legal_moves, Move},
     };
 
     use super::*;
 
+    // The positions the moves end up on
+    fn targets(moves: &[Move]) -> Vec<(i32, i32)> {
+        moves
+            .iter()
+            .map(|piece_move| (piece_move.to.x(), piece_move.to.y()))
+            .collect()
+    }
+
+    fn square(x: i32, y: i32) -> Square {
+        return Square::new(x, y).unwrap();
+    }
+
     #[test]
     fn init_success() {
         let board = Board::init_board(None);
@@ -264,12 +518,84 @@ mod tests {
         assert_eq!(white_pawns.len(), 8);
     }
 
+    #[test]
+    fn bitboards_follow_the_pieces() {
+        let mut board = Board::init_board(None);
+        assert_eq!(board.occupied().count_ones(), 32);
+        assert_eq!(board.occupancy(Color::WHITE).count_ones(), 16);
+        assert_eq!(board.king_square(Color::BLACK), Some(square(4, 0)));
+
+        // e2 to e4
+        let pawn = board[square(4, 6)];
+        board.set_piece(square(4, 4), pawn);
+        board.set_piece(square(4, 6), Board::empty()[square(4, 6)]);
+        let white_pawns = board.pieces_of(Color::WHITE, PieceType::PAWN);
+        assert!(contains(white_pawns, square(4, 4)));
+        assert!(!contains(white_pawns, square(4, 6)));
+        assert_eq!(board.occupied().count_ones(), 32);
+
+        // Only the pawn attacks d5, the queen now sees e2 but not d5
+        assert_eq!(
+            board.attackers(square(3, 3), Color::WHITE),
+            square_set(square(4, 4))
+        );
+        assert_eq!(board.attackers(square(4, 6), Color::WHITE).count_ones(), 4);
+
+        let rebuilt = Board::from_pieces(board.pieces);
+        for color in [Color::WHITE, Color::BLACK] {
+            assert_eq!(rebuilt.occupancy(color), board.occupancy(color));
+        }
+    }
+
+    #[test]
+    fn checkers_and_pinned_pieces() {
+        // The rook on e4 gives check and the bishop on b4 pins the knight on d2
+        let board = parse_fen_string(String::from("4k3/8/8/8/1b2r3/8/3N1B2/4K3")).unwrap();
+        assert_eq!(board.checkers(Color::WHITE), square_set(square(4, 4)));
+        assert_eq!(board.pinned(Color::WHITE), square_set(square(3, 6)));
+        assert_eq!(board.checkers(Color::BLACK), 0);
+        assert_eq!(board.pinned(Color::BLACK), 0);
+
+        // The pinned knight can not block, the bishop can only block on e3
+        assert!(get_legal_moves(board, square(3, 6), Color::WHITE).is_empty());
+        assert_eq!(
+            targets(&get_legal_moves(board, square(5, 6), Color::WHITE)),
+            vec![(4, 5)]
+        );
+        // The king can not step back along the file of the rook
+        let mut king_moves = targets(&get_legal_moves(board, square(4, 7), Color::WHITE));
+        king_moves.sort();
+        assert_eq!(king_moves, vec![(3, 7), (5, 7)]);
+    }
+
+    #[test]
+    fn attack_and_defence_map() {
+        let board = parse_fen_string(String::from("4k3/8/8/3p4/4P3/2N5/8/4K3")).unwrap();
+        let map = board.attack_map();
+        let (c3, d5, e4) = (square(2, 5), square(3, 3), square(4, 4));
+
+        assert_eq!(
+            map.attackers(d5, Color::WHITE),
+            square_set(c3) | square_set(e4)
+        );
+        assert_eq!(map.count(d5, Color::WHITE), 2);
+        assert_eq!(map.count(d5, Color::BLACK), 0);
+        // The knight defends the pawn on e4
+        assert_eq!(map.attackers(e4, Color::WHITE), square_set(c3));
+        assert_eq!(map.attackers(e4, Color::BLACK), square_set(d5));
+        assert!(contains(map.attacked_squares(Color::BLACK), e4));
+        assert!(!contains(map.attacked_squares(Color::BLACK), d5));
+
+        assert_eq!(map.hanging(&board, Color::BLACK), square_set(d5));
+        assert_eq!(map.hanging(&board, Color::WHITE), 0);
+    }
+
     #[test]
     fn bishop_no_moves_game_start() {
         let board = Board::init_board(None);
         let piece_size = board.pieces.len();
         println!("{piece_size}");
-        let legal_moves = get_legal_moves(board, 2, 0, Color::WHITE);
+        let legal_moves = get_legal_moves(board, square(2, 0), Color::WHITE);
 
         assert_eq!(legal_moves.len(), 0);
     }
@@ -278,12 +604,12 @@ mod tests {
     fn bishop_can_move() {
         let fen = String::from("rnbqkbnr/1pp1pppp/3p4/p6Q/4P3/7N/PPPP1PPP/RNB1KB1R");
 
-        let board = parser::parse_fen_string(fen);
+        let board = parser::parse_fen_string(fen).unwrap();
 
-        let white_bishop_legal_moves = get_legal_moves(board, 5, 7, Color::WHITE);
+        let white_bishop_legal_moves = get_legal_moves(board, square(5, 7), Color::WHITE);
         // White legal moves should be: (4, 6) (3, 5) (2, 4) (1, 3) (0, 2)
 
-        let black_bishop_legal_moves = get_legal_moves(board, 2, 0, Color::BLACK);
+        let black_bishop_legal_moves = get_legal_moves(board, square(2, 0), Color::BLACK);
 
         assert_eq!(white_bishop_legal_moves.len(), 5);
         assert_eq!(black_bishop_legal_moves.len(), 5);
@@ -292,126 +618,124 @@ mod tests {
     fn test_situation() {
         let fen = String::from("rnbqkbnr/2p1pppp/1p1p4/p6Q/4P3/7N/PPPP1PPP/RNB1KB1R");
 
-        let board = parse_fen_string(fen);
-        let white_knight_legal_moves = get_legal_moves(board, 7, 5, Color::BLACK);
+        let board = parse_fen_string(fen).unwrap();
+        let white_knight_legal_moves = get_legal_moves(board, square(7, 5), Color::BLACK);
 
-        let black_bishop_legal_moves = get_legal_moves(board, 2, 0, Color::BLACK);
+        let black_bishop_legal_moves = get_legal_moves(board, square(2, 0), Color::BLACK);
 
         // right: (1,1) (0, 2)
 
-        let white_queen_legal_moves = get_legal_moves(board, 7, 3, Color::WHITE);
-        let white_rook_legal_moves = get_legal_moves(board, 7, 7, Color::WHITE);
-        let black_rook_legal_moves = get_legal_moves(board, 0, 0, Color::BLACK);
-        let white_bishop_legal_moves = get_legal_moves(board, 5, 7, Color::WHITE);
-        let white_pawn_legal_moves = get_legal_moves(board, 0, 6, Color::WHITE);
-        let black_pawn_legal_moves = get_legal_moves(board, 6, 1, Color::BLACK);
+        let white_queen_legal_moves = get_legal_moves(board, square(7, 3), Color::WHITE);
+        let white_rook_legal_moves = get_legal_moves(board, square(7, 7), Color::WHITE);
+        let black_rook_legal_moves = get_legal_moves(board, square(0, 0), Color::BLACK);
+        let white_bishop_legal_moves = get_legal_moves(board, square(5, 7), Color::WHITE);
+        let white_pawn_legal_moves = get_legal_moves(board, square(0, 6), Color::WHITE);
+        let black_pawn_legal_moves = get_legal_moves(board, square(6, 1), Color::BLACK);
 
         assert_eq!(white_rook_legal_moves.len(), 1);
-        assert!(white_rook_legal_moves.contains(&Move(6, 7)));
+        assert!(targets(&white_rook_legal_moves).contains(&(6, 7)));
         assert_eq!(black_rook_legal_moves.len(), 2);
         assert_eq!(black_bishop_legal_moves.len(), 7);
         assert_eq!(white_bishop_legal_moves.len(), 5);
         // Test pawn
 
         assert_eq!(black_pawn_legal_moves.len(), 2);
-        assert!(black_pawn_legal_moves.contains(&Move(6, 2)));
-        assert!(black_pawn_legal_moves.contains(&Move(6, 3)));
+        assert!(targets(&black_pawn_legal_moves).contains(&(6, 2)));
+        assert!(targets(&black_pawn_legal_moves).contains(&(6, 3)));
 
         assert_eq!(white_pawn_legal_moves.len(), 2);
-        assert!(white_pawn_legal_moves.contains(&Move(0, 5)));
-        assert!(white_pawn_legal_moves.contains(&Move(0, 4)));
+        assert!(targets(&white_pawn_legal_moves).contains(&(0, 5)));
+        assert!(targets(&white_pawn_legal_moves).contains(&(0, 4)));
 
         // right: (1,1) (0, 2)
 
         assert_eq!(white_knight_legal_moves.len(), 3);
 
-        let expected_queen_moves: Vec<Move> = vec![
-            Move(6, 3),
-            Move(5, 3),
-            Move(4, 3),
-            Move(3, 3),
-            Move(2, 3),
-            Move(1, 3),
-            Move(0, 3),
-            Move(7, 2),
-            Move(6, 2),
-            Move(7, 4),
-            Move(6, 4),
-            Move(5, 5),
-            Move(4, 6),
-            Move(7, 1),
-            Move(5, 1),
-            Move(3, 7),
+        let expected_queen_moves: Vec<(i32, i32)> = vec![
+            (6, 3),
+            (5, 3),
+            (4, 3),
+            (3, 3),
+            (2, 3),
+            (1, 3),
+            (0, 3),
+            (7, 2),
+            (6, 2),
+            (7, 4),
+            (6, 4),
+            (5, 5),
+            (4, 6),
+            (7, 1),
+            (5, 1),
+            (3, 7),
         ];
         println!("Checking white queen moves:{:?} ", white_queen_legal_moves);
         for piece_move in &expected_queen_moves {
-            assert!(white_queen_legal_moves.contains(piece_move));
+            assert!(targets(&white_queen_legal_moves).contains(piece_move));
         }
         assert_eq!(white_queen_legal_moves.len(), expected_queen_moves.len());
 
         // test that none of the colors are in check
         println!("Looking at white");
-        assert_eq!(in_check(board, Color::WHITE), false);
+        assert!(!in_check(board, Color::WHITE));
         println!("Looking at black");
-        assert_eq!(in_check(board, Color::BLACK), false);
-        assert_eq!(can_pawn_promote(&board, Color::WHITE), None);
-        assert_eq!(can_pawn_promote(&board, Color::BLACK), None);
+        assert!(!in_check(board, Color::BLACK));
     }
 
     #[test]
     fn test_pawn_situation() {
         let fen = String::from("rnbqkbnr/2p1pppp/1p1p4/p3P2Q/8/7N/PPPP1PPP/RNB1KB1R");
-        let board = parser::parse_fen_string(fen);
+        let board = parser::parse_fen_string(fen).unwrap();
 
         for i in 0..8 {
             print_row(board.pieces[i]);
         }
 
-        let white_pawn_legal_moves = get_legal_moves(board, 4, 3, Color::WHITE);
-        let black_pawn_legal_moves = get_legal_moves(board, 3, 2, Color::BLACK);
+        let white_pawn_legal_moves = get_legal_moves(board, square(4, 3), Color::WHITE);
+        let black_pawn_legal_moves = get_legal_moves(board, square(3, 2), Color::BLACK);
         println!("{:?}", board.pieces[3][1].piece_type);
 
         assert_eq!(black_pawn_legal_moves.len(), 2);
-        assert!(black_pawn_legal_moves.contains(&Move(3, 3)));
-        assert!(black_pawn_legal_moves.contains(&Move(4, 3)));
+        assert!(targets(&black_pawn_legal_moves).contains(&(3, 3)));
+        assert!(targets(&black_pawn_legal_moves).contains(&(4, 3)));
 
         assert_eq!(white_pawn_legal_moves.len(), 2);
-        assert!(white_pawn_legal_moves.contains(&Move(4, 2)));
-        assert!(white_pawn_legal_moves.contains(&Move(3, 2)));
+        assert!(targets(&white_pawn_legal_moves).contains(&(4, 2)));
+        assert!(targets(&white_pawn_legal_moves).contains(&(3, 2)));
     }
 
     #[test]
     fn test_checking() {
         let fen = String::from("rnbqkbnr/2p1pppp/1p1p4/pB2P2Q/8/7N/PPPP1PPP/RNB1K2R");
-        let board = parser::parse_fen_string(fen);
+        let board = parser::parse_fen_string(fen).unwrap();
 
-        let black_pawn_legal_moves = get_legal_moves(board, 2, 1, Color::BLACK);
-        let black_knight_legal_moves = get_legal_moves(board, 1, 0, Color::BLACK);
-        let black_queen_legal_moves = get_legal_moves(board, 3, 0, Color::BLACK);
-        let pseudo_moves = get_pseudo_legal_moves(board, 1, 0, Color::BLACK);
+        let black_pawn_legal_moves = get_legal_moves(board, square(2, 1), Color::BLACK);
+        let black_knight_legal_moves = get_legal_moves(board, square(1, 0), Color::BLACK);
+        let black_queen_legal_moves = get_legal_moves(board, square(3, 0), Color::BLACK);
+        let pseudo_moves = get_pseudo_legal_moves(board, square(1, 0), Color::BLACK);
 
         println!("{:?}", board.pieces[0][1].piece_type);
         println!("{:?}", pseudo_moves);
 
         // Only one legal move since that is the only move that gets rid of the check
         assert_eq!(black_pawn_legal_moves.len(), 1);
-        assert!(black_pawn_legal_moves.contains(&Move(2, 2)));
+        assert!(targets(&black_pawn_legal_moves).contains(&(2, 2)));
 
         assert_eq!(black_knight_legal_moves.len(), 2);
-        assert!(black_knight_legal_moves.contains(&Move(2, 2)));
-        assert!(black_knight_legal_moves.contains(&Move(3, 1)));
+        assert!(targets(&black_knight_legal_moves).contains(&(2, 2)));
+        assert!(targets(&black_knight_legal_moves).contains(&(3, 1)));
 
         assert_eq!(black_queen_legal_moves.len(), 1);
-        assert!(black_queen_legal_moves.contains(&Move(3, 1)));
+        assert!(targets(&black_queen_legal_moves).contains(&(3, 1)));
 
-        assert_eq!(in_check(board, Color::BLACK), true);
-        assert_eq!(in_check(board, Color::WHITE), false);
+        assert!(in_check(board, Color::BLACK));
+        assert!(!in_check(board, Color::WHITE));
     }
 
     #[test]
     fn test_white_castling_allowed() {
         let fen = String::from("rnbqkbnr/2p1pppp/1p1p4/pB2P2Q/8/7N/PPPP1PPP/RNB1K2R");
-        let board = parser::parse_fen_string(fen);
+        let board = parser::parse_fen_string(fen).unwrap();
 
         assert_eq!(castle_possible(&board, Color::WHITE), (true, false));
     }
@@ -419,7 +743,7 @@ mod tests {
     #[test]
     fn test_white_castling_not_allowed_intermediate_check() {
         let fen = String::from("rn1qkbnr/2p1pppp/bp1p4/p3P2Q/8/1B5N/PPPP1PPP/RNB1K2R");
-        let board = parser::parse_fen_string(fen);
+        let board = parser::parse_fen_string(fen).unwrap();
 
         assert_eq!(castle_possible(&board, Color::WHITE), (false, false));
     }
@@ -427,9 +751,9 @@ mod tests {
     #[test]
     fn test_white_castling_not_allowed_check() {
         let fen = String::from("rn2kbnr/1bp1pppp/1p1p4/p3P2Q/4q3/1B5N/PPPP1PPP/RNB1K2R");
-        let board = parser::parse_fen_string(fen);
-        let black_queen_legal_moves = get_legal_moves(board, 4, 4, Color::BLACK);
-        let contains_move = black_queen_legal_moves.iter().any(|m| m.eq(&Move(4, 7)));
+        let board = parser::parse_fen_string(fen).unwrap();
+        let black_queen_legal_moves = get_legal_moves(board, square(4, 4), Color::BLACK);
+        let contains_move = targets(&black_queen_legal_moves).contains(&(4, 7));
 
         assert_eq!(castle_possible(&board, Color::WHITE), (false, false));
         // This should fail
@@ -439,7 +763,7 @@ mod tests {
     #[test]
     fn test_black_castling_allowed() {
         let fen = String::from("r3kbnr/1bpqpppp/1pnp4/p3P2Q/2B5/7N/PPPP1PPP/RNB1K2R");
-        let board = parser::parse_fen_string(fen);
+        let board = parser::parse_fen_string(fen).unwrap();
 
         assert_eq!(castle_possible(&board, Color::BLACK), (true, false));
     }
@@ -447,7 +771,7 @@ mod tests {
     #[test]
     fn test_black_castling_not_allowed_check() {
         let fen = String::from("r3kbnr/1bpQpppp/1pnp4/p3P3/2B5/7N/PPPP1PPP/RNB1K2R");
-        let board = parser::parse_fen_string(fen);
+        let board = parser::parse_fen_string(fen).unwrap();
 
         assert_eq!(castle_possible(&board, Color::BLACK), (false, false));
     }
@@ -455,17 +779,64 @@ mod tests {
     #[test]
     fn test_black_castling_not_allowed_intermediate_check() {
         let fen = String::from("r3kbnr/1bQ1pppp/1pnp4/p3P3/2B5/7N/PPPP1PPP/RNB1K2R");
-        let board = parser::parse_fen_string(fen);
+        let board = parser::parse_fen_string(fen).unwrap();
 
         assert_eq!(castle_possible(&board, Color::BLACK), (false, false));
     }
 
+    #[test]
+    fn dead_positions_have_insufficient_material() {
+        let dead_positions = [
+            // king against king
+            "8/8/4k3/8/8/4K3/8/8",
+            // king and bishop against king
+            "8/8/4k3/8/8/4KB2/8/8",
+            // king and knight against king
+            "8/8/4k3/8/2n5/4K3/8/8",
+            // bishops on the same square color
+            "8/8/2b1k3/8/8/4KB2/8/8",
+        ];
+        for fen in dead_positions {
+            let board = parse_fen_string(String::from(fen)).unwrap();
+            assert!(insufficient_material(&board), "{}", fen);
+        }
+
+        let playable_positions = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR",
+            // a single pawn can still promote
+            "8/8/4k3/8/8/4K3/4P3/8",
+            // bishops on different square colors
+            "8/8/3bk3/8/8/4KB2/8/8",
+            // two knights against a knight, a mate is possible if the other side helps
+            "8/8/4k3/5n2/8/4KN2/8/8",
+            "8/8/4k3/8/8/4KNN1/8/8",
+        ];
+        for fen in playable_positions {
+            let board = parse_fen_string(String::from(fen)).unwrap();
+            assert!(!insufficient_material(&board), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn insufficient_material_for_one_side() {
+        // A lone king can never win on time
+        let board = parse_fen_string(String::from("8/8/4k3/8/8/4K3/8/7Q")).unwrap();
+        assert!(insufficient_material_for(&board, Color::BLACK));
+        assert!(!insufficient_material_for(&board, Color::WHITE));
+
+        // A knight can mate if the other side has a rook to block its own king
+        let board = parse_fen_string(String::from("8/8/4k3/8/8/4KN2/8/7r")).unwrap();
+        assert!(!insufficient_material_for(&board, Color::WHITE));
+        let board = parse_fen_string(String::from("8/8/4k3/8/8/4KN2/8/7q")).unwrap();
+        assert!(insufficient_material_for(&board, Color::WHITE));
+    }
+
     #[test]
     fn rook_no_moves_game_start() {
         let board = Board::init_board(None);
         let piece_size = board.pieces.len();
         println!("{piece_size}");
-        let legal_moves = get_legal_moves(board, 0, 0, Color::WHITE);
+        let legal_moves = get_legal_moves(board, square(0, 0), Color::WHITE);
 
         assert_eq!(legal_moves.len(), 0);
     }
@@ -473,7 +844,7 @@ mod tests {
     #[test]
     fn white_pawn_can_move_from_start() {
         let board = Board::init_board(None);
-        let legal_moves = get_legal_moves(board, 0, 1, Color::WHITE);
+        let legal_moves = get_legal_moves(board, square(0, 1), Color::WHITE);
 
         assert_eq!(legal_moves.len(), 2);
     }
@@ -483,7 +854,7 @@ mod tests {
         let board = Board::init_board(None);
         let piece_size = board.pieces.len();
         println!("{piece_size}");
-        let legal_moves = get_legal_moves(board, 0, 6, Color::BLACK);
+        let legal_moves = get_legal_moves(board, square(0, 6), Color::BLACK);
 
         assert_eq!(legal_moves.len(), 2);
     }
@@ -493,7 +864,7 @@ mod tests {
         let board = Board::init_board(None);
         let piece_size = board.pieces.len();
         println!("{piece_size}");
-        let legal_moves = get_legal_moves(board, 1, 0, Color::WHITE);
+        let legal_moves = get_legal_moves(board, square(1, 0), Color::WHITE);
 
         assert_eq!(legal_moves.len(), 2);
     }
@@ -502,9 +873,9 @@ mod tests {
     fn white_knight_can_move() {
         let fen = String::from("rnbqkbnr/2pppppp/1p6/p6Q/4P3/7N/PPPP1PPP/RNB1KB1R");
 
-        let board = parser::parse_fen_string(fen);
+        let board = parser::parse_fen_string(fen).unwrap();
 
-        let knight_legal_moves = get_legal_moves(board, 7, 5, Color::WHITE);
+        let knight_legal_moves = get_legal_moves(board, square(7, 5), Color::WHITE);
         for piece_move in &knight_legal_moves {
             println!("{:?}", piece_move);
         }
@@ -517,7 +888,7 @@ mod tests {
         let board = Board::init_board(None);
         let piece_size = board.pieces.len();
         println!("{piece_size}");
-        let legal_moves = get_legal_moves(board, 1, 7, Color::BLACK);
+        let legal_moves = get_legal_moves(board, square(1, 7), Color::BLACK);
 
         assert_eq!(legal_moves.len(), 2);
     }
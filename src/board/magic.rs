@@ -0,0 +1,417 @@
+use std::sync::OnceLock;
+
+use super::{bitboard::Bitboard, square::Square};
+
+// Magic bitboards for the sliding pieces.
+// Only the pieces on the rays of a square change its attacks, so those squares (the mask) are
+// multiplied by a magic number that moves them into the top bits without collisions.
+// The top bits are then the index into a table with the attacks for every set of blockers.
+// The magic numbers were found by trying sparse random numbers until no two sets of blockers
+// with different attacks got the same index. The tables are filled the first time they are used
+
+const STRAIGHT_LINES: [(i32, i32); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+const DIAGONALS: [(i32, i32); 4] = [(1, -1), (1, 1), (-1, -1), (-1, 1)];
+
+const ROOK_MAGICS: [u64; 64] = [
+    0x2080002080400010,
+    0x00c0002001401000,
+    0x2100110008402002,
+    0x0880080081041000,
+    0x0200020020041008,
+    0x2300040008010012,
+    0x0c00283004008201,
+    0x0180010000407a80,
+    0x0168800080400020,
+    0x0010400040201000,
+    0x1001002001001048,
+    0x1001002408100100,
+    0x0801000408010012,
+    0x4001000209000400,
+    0x08a20004c8020001,
+    0x2002801145002280,
+    0x0080860021004200,
+    0x001000c009402002,
+    0x00b0002004002800,
+    0x100a808010020800,
+    0x8101010008000410,
+    0x0244008002000480,
+    0x0000040010810208,
+    0x2000020000448534,
+    0x4104400480008033,
+    0x0000810100204000,
+    0x0440430900200010,
+    0x4600240900100100,
+    0x0060080080040080,
+    0x0001000300080400,
+    0x0004084400011002,
+    0x0023040200008041,
+    0x0580050043002080,
+    0x0400804002802008,
+    0x0001002001004010,
+    0x1000200901001000,
+    0x4410800801800c00,
+    0xa012003806001004,
+    0x0020100104008802,
+    0x0004808402000041,
+    0x0010400170898000,
+    0x0080500020004004,
+    0x1040408012020020,
+    0x8010040008004040,
+    0x2001080100110004,
+    0x0000020004008080,
+    0x0021010810040002,
+    0x0800008c43020024,
+    0x0000800021005100,
+    0x0070201040008080,
+    0x0000d04282006a00,
+    0x0010014400080240,
+    0x0001080110050100,
+    0x0012000810240600,
+    0x0402000801040200,
+    0x028100108a004100,
+    0x0050800300102045,
+    0x8208210040120882,
+    0x8010600101183441,
+    0x020b000910006045,
+    0x0241001002480005,
+    0x0081000400880241,
+    0x0000009008024124,
+    0x0048122980410402,
+];
+
+const BISHOP_MAGICS: [u64; 64] = [
+    0x0848020822040013,
+    0x8010a40085821200,
+    0x0008008430840822,
+    0x0808048108040000,
+    0x1304042100008104,
+    0x5001012010204023,
+    0x81048801b8200420,
+    0x200a008084012000,
+    0x0040102001042084,
+    0x840a505042428020,
+    0x0000700102202920,
+    0x44101c0c10800002,
+    0x0040040422000000,
+    0x0180020802090202,
+    0x4020020811041202,
+    0x000104308c042000,
+    0x4140661002424400,
+    0x0028012008010460,
+    0x0188062102002a00,
+    0x0014004840102008,
+    0x0105000290400002,
+    0x8001022200410400,
+    0x104a041918013446,
+    0x008a000082008238,
+    0x04a0060008100430,
+    0x0008220008820801,
+    0x2508041208005010,
+    0x4008080200202020,
+    0x2441001013004000,
+    0x0030008060407000,
+    0x4008108000420800,
+    0x0012021050290100,
+    0x0210080482200500,
+    0xcc01112048100480,
+    0x0020402806500440,
+    0x00048e0080580080,
+    0x0040102020020080,
+    0x0028010440080807,
+    0x4601041108008800,
+    0x8040810e04104200,
+    0x901210110400088a,
+    0xa003080212081050,
+    0x00c1004048401004,
+    0x900000a014400800,
+    0x0008021040405401,
+    0x4020008206002090,
+    0x0004190424030100,
+    0x0424008a02026250,
+    0x8004088250900040,
+    0x1c00430088a04200,
+    0x0001020094040001,
+    0x8040210020880061,
+    0x2010040450442032,
+    0x0800840850044001,
+    0x0004040802140004,
+    0x0004080a04222020,
+    0x8088802110022000,
+    0x1081a10416114400,
+    0x0205010a24060820,
+    0x0000000720411080,
+    0x1008000208430400,
+    0x580c026028810840,
+    0x802020441020a110,
+    0x12c0022401020018,
+];
+
+// The table entries of one square
+#[derive(Clone, Copy, Default)]
+struct Magic {
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    // Where the attacks of the square start in SliderTables::attacks
+    offset: usize,
+}
+
+impl Magic {
+    fn index(&self, occupied: Bitboard) -> usize {
+        return self.offset
+            + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize;
+    }
+}
+
+struct SliderTables {
+    rook: [Magic; 64],
+    bishop: [Magic; 64],
+    attacks: Vec<Bitboard>,
+}
+
+static TABLES: OnceLock<SliderTables> = OnceLock::new();
+
+fn tables() -> &'static SliderTables {
+    return TABLES.get_or_init(|| {
+        let mut attacks = Vec::new();
+        let rook = fill_table(&STRAIGHT_LINES, &ROOK_MAGICS, &mut attacks);
+        let bishop = fill_table(&DIAGONALS, &BISHOP_MAGICS, &mut attacks);
+        return SliderTables {
+            rook,
+            bishop,
+            attacks,
+        };
+    });
+}
+
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    return tables.attacks[tables.rook[square.index()].index(occupied)];
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    return tables.attacks[tables.bishop[square.index()].index(occupied)];
+}
+
+// Walks along each direction one square at a time until the edge of the board or a piece,
+// the square with the piece is included. Slow, only used to fill the tables
+fn walk_attacks(square: Square, occupied: Bitboard, directions: &[(i32, i32)]) -> Bitboard {
+    let mut attacks = 0;
+    for (dx, dy) in directions {
+        let mut current = square;
+        while let Some(next) = current.offset(*dx, *dy) {
+            attacks |= 1 << next.index();
+            if occupied & (1 << next.index()) != 0 {
+                break;
+            }
+            current = next;
+        }
+    }
+    return attacks;
+}
+
+// The squares where a piece changes the attacks, the last square of a ray is always attacked
+fn relevant_squares(square: Square, directions: &[(i32, i32)]) -> Bitboard {
+    let mut mask = 0;
+    for (dx, dy) in directions {
+        let mut current = square;
+        while let Some(next) = current.offset(*dx, *dy) {
+            if next.offset(*dx, *dy).is_none() {
+                break;
+            }
+            mask |= 1 << next.index();
+            current = next;
+        }
+    }
+    return mask;
+}
+
+// Adds the attacks of every square for every set of blockers to the table
+fn fill_table(
+    directions: &[(i32, i32)],
+    magic_numbers: &[u64; 64],
+    attacks: &mut Vec<Bitboard>,
+) -> [Magic; 64] {
+    let mut magics = [Magic::default(); 64];
+    for square in Square::all() {
+        let mask = relevant_squares(square, directions);
+        let magic = Magic {
+            mask,
+            magic: magic_numbers[square.index()],
+            shift: 64 - mask.count_ones(),
+            offset: attacks.len(),
+        };
+        attacks.resize(attacks.len() + (1 << mask.count_ones()), 0);
+
+        // Goes through every subset of the mask
+        let mut blockers: Bitboard = 0;
+        loop {
+            attacks[magic.index(blockers)] = walk_attacks(square, blockers, directions);
+            blockers = blockers.wrapping_sub(mask) & mask;
+            if blockers == 0 {
+                break;
+            }
+        }
+        magics[square.index()] = magic;
+    }
+    return magics;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift random numbers, always the same sequence so a failure can be repeated
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            return self.0;
+        }
+    }
+
+    // Every square on the board in one direction from each square, up to the edge
+    const fn ray_table(dx: i32, dy: i32) -> [Bitboard; 64] {
+        let mut table = [0; 64];
+        let mut index = 0;
+        while index < 64 {
+            let mut x = (index % 8) as i32 + dx;
+            let mut y = (index / 8) as i32 + dy;
+            while 0 <= x && x < 8 && 0 <= y && y < 8 {
+                table[index] |= 1 << (y * 8 + x);
+                x += dx;
+                y += dy;
+            }
+            index += 1;
+        }
+        return table;
+    }
+
+    // The rays that go to higher square indices, the first piece on them is the lowest bit
+    const RAYS_UP: [[Bitboard; 64]; 4] = [
+        ray_table(1, 0),
+        ray_table(-1, 1),
+        ray_table(0, 1),
+        ray_table(1, 1),
+    ];
+    // The rays that go to lower square indices, the first piece on them is the highest bit
+    const RAYS_DOWN: [[Bitboard; 64]; 4] = [
+        ray_table(-1, 0),
+        ray_table(1, -1),
+        ray_table(0, -1),
+        ray_table(-1, -1),
+    ];
+    // Which of the rays are straight lines, the rest are diagonals
+    const STRAIGHT: [bool; 4] = [true, false, true, false];
+
+    // The ray table generator the magic tables replaced, it does not share any code with
+    // walk_attacks so a mistake in one of them shows up as a difference
+    fn slider_attacks(square: Square, occupied: Bitboard, straight: bool) -> Bitboard {
+        let index = square.index();
+        let mut attacks = 0;
+        for direction in 0..4 {
+            if STRAIGHT[direction] != straight {
+                continue;
+            }
+            let ray = RAYS_UP[direction][index];
+            let blockers = ray & occupied;
+            attacks |= if blockers == 0 {
+                ray
+            } else {
+                ray ^ RAYS_UP[direction][blockers.trailing_zeros() as usize]
+            };
+
+            let ray = RAYS_DOWN[direction][index];
+            let blockers = ray & occupied;
+            attacks |= if blockers == 0 {
+                ray
+            } else {
+                ray ^ RAYS_DOWN[direction][63 - blockers.leading_zeros() as usize]
+            };
+        }
+        return attacks;
+    }
+
+    // Every set of blockers has to give the attacks of walking the rays,
+    // two sets may only share an entry if their attacks are the same
+    #[test]
+    fn magics_have_no_collisions() {
+        for (directions, magic_numbers) in
+            [(STRAIGHT_LINES, ROOK_MAGICS), (DIAGONALS, BISHOP_MAGICS)]
+        {
+            for square in Square::all() {
+                let mask = relevant_squares(square, &directions);
+                let magic = Magic {
+                    mask,
+                    magic: magic_numbers[square.index()],
+                    shift: 64 - mask.count_ones(),
+                    offset: 0,
+                };
+                let mut table = vec![None; 1 << mask.count_ones()];
+                let mut blockers: Bitboard = 0;
+                loop {
+                    let square_attacks = walk_attacks(square, blockers, &directions);
+                    let entry = &mut table[magic.index(blockers)];
+                    assert!(
+                        entry.is_none() || *entry == Some(square_attacks),
+                        "collision on {}",
+                        square
+                    );
+                    *entry = Some(square_attacks);
+                    blockers = blockers.wrapping_sub(mask) & mask;
+                    if blockers == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn magics_match_the_ray_tables() {
+        let mut random = Random(0x2545_F491_4F6C_DD1D);
+        for square in Square::all() {
+            for _ in 0..200 {
+                // Both sparse and crowded boards
+                let occupied = random.next() & random.next();
+                assert_eq!(
+                    rook_attacks(square, occupied),
+                    slider_attacks(square, occupied, true),
+                    "rook on {} with {:#x}",
+                    square,
+                    occupied
+                );
+                assert_eq!(
+                    bishop_attacks(square, occupied),
+                    slider_attacks(square, occupied, false),
+                    "bishop on {} with {:#x}",
+                    square,
+                    occupied
+                );
+                let occupied = random.next();
+                assert_eq!(
+                    rook_attacks(square, occupied),
+                    slider_attacks(square, occupied, true)
+                );
+                assert_eq!(
+                    bishop_attacks(square, occupied),
+                    slider_attacks(square, occupied, false)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn table_sizes() {
+        let tables = tables();
+        // 12 bits for a rook in the corner, 9 for a bishop in the middle
+        assert_eq!(tables.rook[0].mask.count_ones(), 12);
+        assert_eq!(tables.bishop[27].mask.count_ones(), 9);
+        assert_eq!(tables.bishop[0].mask.count_ones(), 6);
+        assert_eq!(tables.attacks.len(), 102_400 + 5_248);
+    }
+}
@@ -1,3 +1,8 @@
+pub mod bitboard;
+#[allow(clippy::module_inception)]
 pub mod board;
+mod magic;
 pub mod parser;
 pub mod pieces;
+pub mod square;
+pub(crate) mod zobrist;
@@ -1,13 +1,176 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, error::Error, fmt};
 
 use super::{
-    board::{self, Board},
-    pieces::{Color, Piece, PieceType},
+    board::{in_check, Board},
+    pieces::{initial_castling_rights, Color, Piece, PieceType},
+    square::Square,
 };
 
-//fn serialize_board_fen(board: Board) -> str {}
+// The six fields of a fen string, in order
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FenField {
+    PLACEMENT,
+    TURN,
+    CASTLING,
+    ENPASSANT,
+    HALFMOVECLOCK,
+    FULLMOVENUMBER,
+}
+
+impl fmt::Display for FenField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FenField::PLACEMENT => "piece placement",
+            FenField::TURN => "side to move",
+            FenField::CASTLING => "castling rights",
+            FenField::ENPASSANT => "en passant",
+            FenField::HALFMOVECLOCK => "halfmove clock",
+            FenField::FULLMOVENUMBER => "fullmove number",
+        };
+        return write!(f, "{}", name);
+    }
+}
+
+// Why a fen string could not be read
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FenError {
+    pub field: FenField,
+    // The rank as written on the board, 8 is the first rank in the fen string
+    pub rank: Option<u32>,
+    // The character that was not expected, None if something is missing
+    pub character: Option<char>,
+    pub expected: &'static str,
+}
+
+impl FenError {
+    fn new(field: FenField, expected: &'static str) -> FenError {
+        return FenError {
+            field,
+            rank: None,
+            character: None,
+            expected,
+        };
+    }
+
+    // An error for a whole field, points at its first character
+    fn at(field: FenField, value: &str, expected: &'static str) -> FenError {
+        return FenError {
+            field,
+            rank: None,
+            character: value.chars().next(),
+            expected,
+        };
+    }
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid {}", self.field)?;
+        if let Some(rank) = self.rank {
+            write!(f, " on rank {}", rank)?;
+        }
+        if let Some(character) = self.character {
+            write!(f, " at '{}'", character)?;
+        }
+        return write!(f, ", expected {}", self.expected);
+    }
+}
+
+impl Error for FenError {}
+
+// All six fields of a fen string
+#[derive(Clone, Copy, Debug)]
+pub struct Fen {
+    pub board: Board,
+    pub turn: Color,
+    // (right, left) like Game::can_castle_white
+    pub can_castle_white: (bool, bool),
+    pub can_castle_black: (bool, bool),
+    pub en_passant: Option<Square>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+}
+
+// The piece placement field, from rank 8 down to rank 1
+pub fn serialize_board_fen(board: &Board) -> String {
+    let mut rows: Vec<String> = Vec::new();
+    for row in board.pieces().iter() {
+        let mut fen_row = String::new();
+        let mut empty_spots = 0;
+        for piece in row.iter() {
+            if piece.piece_type == PieceType::EMPTY {
+                empty_spots += 1;
+                continue;
+            }
+            if empty_spots > 0 {
+                fen_row.push_str(&empty_spots.to_string());
+                empty_spots = 0;
+            }
+            fen_row.push(piece_char(piece));
+        }
+        if empty_spots > 0 {
+            fen_row.push_str(&empty_spots.to_string());
+        }
+        rows.push(fen_row);
+    }
+    return rows.join("/");
+}
+
+// uppercase characters = white, and lowercase characters = black
+fn piece_char(piece: &Piece) -> char {
+    let c = match piece.piece_type {
+        PieceType::PAWN => 'p',
+        PieceType::KNIGHT => 'n',
+        PieceType::BISHOP => 'b',
+        PieceType::ROOK => 'r',
+        PieceType::QUEEN => 'q',
+        PieceType::KING => 'k',
+        PieceType::EMPTY => ' ',
+    };
+    if piece.color == Color::WHITE {
+        return c.to_ascii_uppercase();
+    }
+    return c;
+}
+
+// Writes all six fields, the opposite of parse_fen
+pub fn serialize_fen(fen: &Fen) -> String {
+    let turn = if fen.turn == Color::BLACK { "b" } else { "w" };
+
+    let mut castling = String::new();
+    if fen.can_castle_white.0 {
+        castling.push('K');
+    }
+    if fen.can_castle_white.1 {
+        castling.push('Q');
+    }
+    if fen.can_castle_black.1 {
+        castling.push('k');
+    }
+    if fen.can_castle_black.0 {
+        castling.push('q');
+    }
+    if castling.is_empty() {
+        castling.push('-');
+    }
 
-pub fn parse_fen_string(fen_string: String) -> Board {
+    let en_passant = match fen.en_passant {
+        Some(square) => square.to_string(),
+        None => String::from("-"),
+    };
+
+    return format!(
+        "{} {} {} {} {} {}",
+        serialize_board_fen(&fen.board),
+        turn,
+        castling,
+        en_passant,
+        fen.halfmove_clock,
+        fen.fullmove_number
+    );
+}
+
+pub fn parse_fen_string(fen_string: String) -> Result<Board, FenError> {
     let mut black_piece_map: HashMap<char, PieceType> = HashMap::new();
     black_piece_map.insert('p', PieceType::PAWN);
     black_piece_map.insert('n', PieceType::KNIGHT);
@@ -25,10 +188,15 @@ pub fn parse_fen_string(fen_string: String) -> Board {
     white_piece_map.insert('R', PieceType::ROOK);
 
     let mut fen_string_parts = fen_string.split_whitespace();
-    let piece_placement = fen_string_parts.next().unwrap();
+    let piece_placement = fen_string_parts
+        .next()
+        .ok_or(FenError::new(FenField::PLACEMENT, "the piece placement"))?;
 
     // Parse piece placement
-    let ranks = piece_placement.split('/');
+    let ranks: Vec<&str> = piece_placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::new(FenField::PLACEMENT, "8 ranks separated by /"));
+    }
 
     let mut board_pieces: [[Piece; 8]; 8] = [[Piece {
         color: Color::EMPTY,
@@ -36,51 +204,325 @@ pub fn parse_fen_string(fen_string: String) -> Board {
         has_moved: false,
     }; 8]; 8];
 
-    for (rank, pieces) in ranks.enumerate() {
+    for (rank, pieces) in ranks.iter().enumerate() {
         // uppercase characters = white, and lowercase characters = black
         let mut board_row: [Piece; 8] = [Piece {
             color: Color::EMPTY,
             piece_type: PieceType::EMPTY,
             has_moved: false,
         }; 8];
+        let rank_error = |character: Option<char>, expected: &'static str| FenError {
+            field: FenField::PLACEMENT,
+            rank: Some(8 - rank as u32),
+            character,
+            expected,
+        };
 
-        let mut offset_by_empty_spots: usize = 0;
-        for (file, piece) in pieces.chars().enumerate() {
-            if piece.is_numeric() {
-                let n_empty_spots = piece.to_digit(10).unwrap() - 1;
-
-                for i in 0..n_empty_spots {
-                    let i: usize = i as usize;
-                    board_row[(file + i) as usize] = Piece {
-                        color: Color::EMPTY,
-                        piece_type: PieceType::EMPTY,
-                        has_moved: false,
-                    };
+        let mut file: usize = 0;
+        for piece in pieces.chars() {
+            if file >= 8 {
+                return Err(rank_error(Some(piece), "8 squares in the rank"));
+            }
+            if let Some(n_empty_spots) = piece.to_digit(10) {
+                if !(1..=8).contains(&n_empty_spots) {
+                    return Err(rank_error(
+                        Some(piece),
+                        "a number of empty squares from 1 to 8",
+                    ));
+                }
+                // The board is already empty
+                file += n_empty_spots as usize;
+                if file > 8 {
+                    return Err(rank_error(Some(piece), "8 squares in the rank"));
                 }
-                offset_by_empty_spots = offset_by_empty_spots + (n_empty_spots as usize);
-            } else if black_piece_map.get(&piece).is_some() {
+            } else if black_piece_map.contains_key(&piece) {
                 let piece_type = *(black_piece_map.get(&piece).unwrap());
-                board_row[file + offset_by_empty_spots] = Piece {
+                board_row[file] = Piece {
                     color: Color::BLACK,
                     has_moved: piece_type == PieceType::PAWN && rank != 1,
                     piece_type,
                 };
-            } else if white_piece_map.get(&piece).is_some() {
+                file += 1;
+            } else if white_piece_map.contains_key(&piece) {
                 let piece_type = *(white_piece_map.get(&piece).unwrap());
-                board_row[file + offset_by_empty_spots] = Piece {
+                board_row[file] = Piece {
                     color: Color::WHITE,
                     has_moved: piece_type == PieceType::PAWN && rank != 6,
                     piece_type,
                 };
+                file += 1;
+            } else {
+                return Err(rank_error(
+                    Some(piece),
+                    "one of pnbrqkPNBRQK or a number of empty squares",
+                ));
             }
         }
+        if file < 8 {
+            return Err(rank_error(None, "8 squares in the rank"));
+        }
 
         board_pieces[rank] = board_row;
     }
-    let board = Board {
-        pieces: board_pieces,
+    let board = Board::from_pieces(board_pieces);
+    return Ok(board);
+}
+
+// Parses a complete fen string with all six fields and checks that the position can be played
+pub fn parse_fen(fen_string: &str) -> Result<Fen, FenError> {
+    return read_fen(fen_string, false);
+}
+
+// Like parse_fen, but fields that are missing at the end get the values of a new game,
+// except castling rights which are then given to every king and rook that still stands
+// on its starting position, and the turn which goes to black if black is in check.
+// Only used by Game::new, so a position can be set up quickly
+pub(crate) fn parse_fen_with_defaults(fen_string: &str) -> Result<Fen, FenError> {
+    return read_fen(fen_string, true);
+}
+
+fn read_fen(fen_string: &str, defaults: bool) -> Result<Fen, FenError> {
+    // A field that is missing is an error unless the defaults may be used
+    let missing = |field, expected| {
+        if defaults {
+            return Ok(());
+        }
+        return Err(FenError::new(field, expected));
     };
-    return board;
+    let mut board = parse_fen_string(String::from(fen_string))?;
+    validate_pieces(&board)?;
+    let mut fields = fen_string.split_whitespace().skip(1);
+
+    let turn_field = fields.next();
+    let turn = match turn_field {
+        // Without the field it is white's turn, unless only black can be in check
+        None => {
+            missing(FenField::TURN, "w or b")?;
+            if in_check(board, Color::BLACK) {
+                Color::BLACK
+            } else {
+                Color::WHITE
+            }
+        }
+        Some("w") => Color::WHITE,
+        Some("b") => Color::BLACK,
+        Some(field) => return Err(FenError::at(FenField::TURN, field, "w or b")),
+    };
+    // The side that just moved can not have left its king in check
+    let moved_color = if turn == Color::WHITE {
+        Color::BLACK
+    } else {
+        Color::WHITE
+    };
+    if in_check(board, moved_color) {
+        return Err(FenError::at(
+            FenField::TURN,
+            turn_field.unwrap_or(""),
+            "the side not to move to not be in check",
+        ));
+    }
+
+    let mut can_castle_white = initial_castling_rights(&board, Color::WHITE);
+    let mut can_castle_black = initial_castling_rights(&board, Color::BLACK);
+    let castling_field = fields.next();
+    if castling_field.is_none() {
+        missing(FenField::CASTLING, "- or the castling rights")?;
+    }
+    if let Some(castling) = castling_field {
+        let rights = parse_castling(castling)?;
+        for (letter, has_right, possible) in [
+            ('K', rights[0].0, can_castle_white.0),
+            ('Q', rights[0].1, can_castle_white.1),
+            ('q', rights[1].0, can_castle_black.0),
+            ('k', rights[1].1, can_castle_black.1),
+        ] {
+            if has_right && !possible {
+                return Err(FenError {
+                    field: FenField::CASTLING,
+                    rank: None,
+                    character: Some(letter),
+                    expected: "the king and rook on their starting squares",
+                });
+            }
+        }
+        can_castle_white = rights[0];
+        can_castle_black = rights[1];
+        apply_castling_rights(&mut board, Color::WHITE, can_castle_white);
+        apply_castling_rights(&mut board, Color::BLACK, can_castle_black);
+    }
+
+    let en_passant = match fields.next() {
+        None => {
+            missing(FenField::ENPASSANT, "- or the en passant position")?;
+            None
+        }
+        Some("-") => None,
+        Some(field) => Some(parse_en_passant(&board, turn, field)?),
+    };
+
+    let halfmove_clock = match fields.next() {
+        None => {
+            missing(FenField::HALFMOVECLOCK, "a number")?;
+            0
+        }
+        Some(field) => field
+            .parse()
+            .map_err(|_| FenError::at(FenField::HALFMOVECLOCK, field, "a number"))?,
+    };
+    let fullmove_number = match fields.next() {
+        None => {
+            missing(FenField::FULLMOVENUMBER, "a number from 1")?;
+            1
+        }
+        Some(field) => match field.parse() {
+            Ok(number) if number >= 1 => number,
+            _ => {
+                return Err(FenError::at(
+                    FenField::FULLMOVENUMBER,
+                    field,
+                    "a number from 1",
+                ))
+            }
+        },
+    };
+    if let Some(field) = fields.next() {
+        return Err(FenError::at(
+            FenField::FULLMOVENUMBER,
+            field,
+            "the end of the fen string",
+        ));
+    }
+
+    return Ok(Fen {
+        board,
+        turn,
+        can_castle_white,
+        can_castle_black,
+        en_passant,
+        halfmove_clock,
+        fullmove_number,
+    });
+}
+
+// Exactly one king per side and no pawns on the first and last rank
+fn validate_pieces(board: &Board) -> Result<(), FenError> {
+    for (color, king) in [(Color::WHITE, 'K'), (Color::BLACK, 'k')] {
+        let kings = board
+            .pieces()
+            .iter()
+            .flatten()
+            .filter(|piece| piece.piece_type == PieceType::KING && piece.color == color)
+            .count();
+        if kings != 1 {
+            return Err(FenError {
+                field: FenField::PLACEMENT,
+                rank: None,
+                character: Some(king),
+                expected: "exactly one king per side",
+            });
+        }
+    }
+
+    for row in [0, 7] {
+        if let Some(pawn) = board.pieces()[row]
+            .iter()
+            .find(|piece| piece.piece_type == PieceType::PAWN)
+        {
+            return Err(FenError {
+                field: FenField::PLACEMENT,
+                rank: Some(8 - row as u32),
+                character: Some(if pawn.color == Color::WHITE { 'P' } else { 'p' }),
+                expected: "no pawns on the first and last rank",
+            });
+        }
+    }
+    return Ok(());
+}
+
+// Reads the castling field into [(right, left) for white, (right, left) for black]
+fn parse_castling(field: &str) -> Result<[(bool, bool); 2], FenError> {
+    let mut white = (false, false);
+    let mut black = (false, false);
+    if field == "-" {
+        return Ok([white, black]);
+    }
+    for letter in field.chars() {
+        let right = match letter {
+            'K' => &mut white.0,
+            'Q' => &mut white.1,
+            'q' => &mut black.0,
+            'k' => &mut black.1,
+            _ => {
+                return Err(FenError {
+                    field: FenField::CASTLING,
+                    rank: None,
+                    character: Some(letter),
+                    expected: "- or each of KQkq at most once",
+                })
+            }
+        };
+        if *right {
+            return Err(FenError {
+                field: FenField::CASTLING,
+                rank: None,
+                character: Some(letter),
+                expected: "- or each of KQkq at most once",
+            });
+        }
+        *right = true;
+    }
+    return Ok([white, black]);
+}
+
+// The en passant position has to be right behind a pawn that just moved two squares
+fn parse_en_passant(board: &Board, turn: Color, field: &str) -> Result<Square, FenError> {
+    let error = FenError::at(
+        FenField::ENPASSANT,
+        field,
+        "- or the position behind a pawn that just moved two squares",
+    );
+    let square: Square = field.parse().map_err(|_| error)?;
+    let (x, y) = (square.x(), square.y());
+    // The pawn moved from start_row over row to pawn_row, so it left both behind empty
+    let (start_row, row, pawn_row, moved_color) = if turn == Color::WHITE {
+        (1, 2, 3, Color::BLACK)
+    } else {
+        (6, 5, 4, Color::WHITE)
+    };
+    let piece_on_row = |row: i32| board[Square::new(x, row).expect("x is a file on the board")];
+    let pawn = piece_on_row(pawn_row);
+    if y != row
+        || piece_on_row(row).piece_type != PieceType::EMPTY
+        || piece_on_row(start_row).piece_type != PieceType::EMPTY
+        || pawn.piece_type != PieceType::PAWN
+        || pawn.color != moved_color
+    {
+        return Err(error);
+    }
+    return Ok(square);
+}
+
+// Marks the king and rooks as moved when the side has lost the right to castle with them,
+// since castle_possible only looks at the board
+fn apply_castling_rights(board: &mut Board, color: Color, rights: (bool, bool)) {
+    let (row, right_rook, left_rook) = if color == Color::WHITE {
+        (7, 7, 0)
+    } else {
+        (0, 0, 7)
+    };
+    for (x, piece_type, has_moved) in [
+        (4, PieceType::KING, !rights.0 && !rights.1),
+        (right_rook, PieceType::ROOK, !rights.0),
+        (left_rook, PieceType::ROOK, !rights.1),
+    ] {
+        let Some(square) = Square::new(x, row) else {
+            continue;
+        };
+        let piece = board[square];
+        if piece.piece_type == piece_type && piece.color == color {
+            board.set_piece(square, Piece { has_moved, ..piece });
+        }
+    }
 }
 
 pub fn print_row(pieces: [Piece; 8]) {
@@ -104,7 +546,15 @@ mod tests {
         pieces::{Color, PieceType},
     };
 
-    use super::parse_fen_string;
+    use super::{
+        parse_fen, parse_fen_string, parse_fen_with_defaults, serialize_board_fen, serialize_fen,
+        FenError, FenField,
+    };
+    use crate::board::square::Square;
+
+    fn sq(name: &str) -> Square {
+        return name.parse().unwrap();
+    }
 
     #[test]
     fn board_creation_success() {
@@ -120,14 +570,215 @@ mod tests {
             }
         }
 
-        let board = parse_fen_string(fen);
+        let board = parse_fen_string(fen).unwrap();
         for i in 0..8 {
-            print_row(board.pieces[i]);
+            print_row(board.pieces()[i]);
+        }
+        assert_eq!(board.pieces().len(), 8);
+        assert_ne!(board[sq("b8")].piece_type, PieceType::EMPTY);
+        assert_eq!(board[sq("b8")].piece_type, PieceType::KNIGHT);
+        assert_eq!(board[sq("e4")].piece_type, PieceType::PAWN);
+        assert_eq!(board[sq("e4")].color, Color::WHITE);
+    }
+
+    #[test]
+    fn parse_all_fields() {
+        let fen = parse_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w Kq d6 0 12").unwrap();
+
+        assert_eq!(fen.turn, Color::WHITE);
+        assert_eq!(fen.can_castle_white, (true, false));
+        assert_eq!(fen.can_castle_black, (true, false));
+        assert_eq!(fen.en_passant, "d6".parse().ok());
+        assert_eq!((fen.halfmove_clock, fen.fullmove_number), (0, 12));
+        // Rooks that can not castle anymore count as moved
+        assert!(!fen.board[sq("h1")].has_moved);
+        assert!(fen.board[sq("a1")].has_moved);
+        assert!(fen.board[sq("h8")].has_moved);
+        assert!(!fen.board[sq("a8")].has_moved);
+        assert!(!fen.board[sq("e8")].has_moved);
+
+        let fen = parse_fen("r3k2r/8/8/8/8/8/8/R3K2R b - - 3 40").unwrap();
+        assert_eq!(fen.turn, Color::BLACK);
+        assert_eq!(fen.can_castle_white, (false, false));
+        assert_eq!(fen.can_castle_black, (false, false));
+        assert_eq!(fen.en_passant, None);
+        assert!(fen.board[sq("e1")].has_moved);
+    }
+
+    #[test]
+    fn serialize_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "r3k2r/8/8/3pP3/8/8/8/R3K2R w Kq d6 0 12",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Qk - 3 40",
+            "8/8/4k3/8/8/4K3/8/8 w - - 37 82",
+        ];
+        for fen in fens {
+            assert_eq!(serialize_fen(&parse_fen(fen).unwrap()), fen);
         }
-        assert_eq!(board.pieces.len(), 8);
-        assert_ne!(board.pieces[0][1].piece_type, PieceType::EMPTY);
-        assert_eq!(board.pieces[0][1].piece_type, PieceType::KNIGHT);
-        assert_eq!(board.pieces[4][4].piece_type, PieceType::PAWN);
-        assert_eq!(board.pieces[4][4].color, Color::WHITE);
+
+        let board = parse_fen_string(String::from("8/8/4k3/8/8/4K3/8/8")).unwrap();
+        assert_eq!(serialize_board_fen(&board), "8/8/4k3/8/8/4K3/8/8");
+    }
+
+    #[test]
+    fn parse_syntax_errors() {
+        let error = |field, rank, character, expected| FenError {
+            field,
+            rank,
+            character,
+            expected,
+        };
+        assert_eq!(
+            parse_fen("").unwrap_err(),
+            error(FenField::PLACEMENT, None, None, "the piece placement")
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/4K3 w - - 0 1").unwrap_err(),
+            error(FenField::PLACEMENT, None, None, "8 ranks separated by /")
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/4K4 w - - 0 1").unwrap_err(),
+            error(
+                FenField::PLACEMENT,
+                Some(1),
+                Some('4'),
+                "8 squares in the rank"
+            )
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/4K2 w - - 0 1").unwrap_err(),
+            error(FenField::PLACEMENT, Some(1), None, "8 squares in the rank")
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/3x4/8/8/8/4K3 w - - 0 1").unwrap_err(),
+            error(
+                FenField::PLACEMENT,
+                Some(5),
+                Some('x'),
+                "one of pnbrqkPNBRQK or a number of empty squares"
+            )
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/4K3 x - - 0 1").unwrap_err(),
+            error(FenField::TURN, None, Some('x'), "w or b")
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/4K3 w KK - 0 1")
+                .unwrap_err()
+                .field,
+            FenField::CASTLING
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/4K3 w - e9 0 1")
+                .unwrap_err()
+                .field,
+            FenField::ENPASSANT
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/4K3 w - - x 1")
+                .unwrap_err()
+                .field,
+            FenField::HALFMOVECLOCK
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 0")
+                .unwrap_err()
+                .field,
+            FenField::FULLMOVENUMBER
+        );
+        // Every field has to be there
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/4K3").unwrap_err(),
+            error(FenField::TURN, None, None, "w or b")
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/4K3 w -").unwrap_err().field,
+            FenField::ENPASSANT
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/4K3 w - - 0").unwrap_err(),
+            error(FenField::FULLMOVENUMBER, None, None, "a number from 1")
+        );
+        assert!(parse_fen_with_defaults("4k3/8/8/8/8/8/8/4K3 w -").is_ok());
+        // A missing turn goes to the side in check
+        let fen = parse_fen_with_defaults("4k3/8/8/8/8/8/8/4RK2").unwrap();
+        assert_eq!(fen.turn, Color::BLACK);
+        let fen = parse_fen_with_defaults("4k3/8/8/8/8/8/8/4K3").unwrap();
+        assert_eq!(fen.turn, Color::WHITE);
+        assert_eq!(
+            parse_fen_with_defaults("4k3/8/8/8/8/8/8/4RK2 w")
+                .unwrap_err()
+                .field,
+            FenField::TURN
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1 x")
+                .unwrap_err()
+                .field,
+            FenField::FULLMOVENUMBER
+        );
+    }
+
+    #[test]
+    fn parse_impossible_positions() {
+        // Kings
+        assert_eq!(
+            parse_fen("8/8/8/8/8/8/8/4K3 w - - 0 1")
+                .unwrap_err()
+                .character,
+            Some('k')
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/3KK3 w - - 0 1")
+                .unwrap_err()
+                .character,
+            Some('K')
+        );
+        // Pawns on the back ranks
+        let error = parse_fen("4k2P/8/8/8/8/8/8/4K3 w - - 0 1").unwrap_err();
+        assert_eq!((error.rank, error.character), (Some(8), Some('P')));
+        // Black is in check but it is white's turn
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/4RK2 w - - 0 1")
+                .unwrap_err()
+                .field,
+            FenField::TURN
+        );
+        assert!(parse_fen("4k3/8/8/8/8/8/8/4RK2 b - - 0 1").is_ok());
+        // The rook for white's queen side castling has moved
+        let error = parse_fen("r3k2r/8/8/8/8/8/8/1R2K2R w KQkq - 0 1").unwrap_err();
+        assert_eq!(
+            (error.field, error.character),
+            (FenField::CASTLING, Some('Q'))
+        );
+        // No pawn that could have moved two squares
+        assert_eq!(
+            parse_fen("4k3/8/8/8/8/8/8/4K3 b - e3 0 1")
+                .unwrap_err()
+                .field,
+            FenField::ENPASSANT
+        );
+        assert_eq!(
+            parse_fen("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1")
+                .unwrap_err()
+                .field,
+            FenField::ENPASSANT
+        );
+        // The pawn could not have come from d7 or passed over d6
+        assert_eq!(
+            parse_fen("4k3/3p4/8/3pP3/8/8/8/4K3 w - d6 0 1")
+                .unwrap_err()
+                .field,
+            FenField::ENPASSANT
+        );
+        assert_eq!(
+            parse_fen("4k3/8/3n4/3pP3/8/8/8/4K3 w - d6 0 1")
+                .unwrap_err()
+                .field,
+            FenField::ENPASSANT
+        );
+        assert!(parse_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").is_ok());
     }
 }
@@ -1,13 +1,14 @@
-use std::fmt::Error;
-
-use crate::game::Game;
-use crate::utils::matrix::index_to_col_row;
-use crate::utils::sets::cartesian_product;
-
-use super::board::{in_check, in_check_mate, positions_in_check, Board};
+use super::bitboard::{
+    between, bishop_attacks, contains, king_attacks, knight_attacks, line, pawn_attacks,
+    queen_attacks, rook_attacks, squares, Bitboard,
+};
+use super::board::{in_check, in_check_mate, in_stalemate, positions_in_check, Board};
+use super::square::Square;
+use crate::error::{CastlingReason, ChessError, IllegalMoveReason};
+use crate::game::{Game, MoveRecord};
 use std::fmt;
 
-#[derive(Clone, PartialEq, Copy, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Copy, Debug)]
 pub enum PieceType {
     PAWN,
     ROOK,
@@ -18,8 +19,7 @@ pub enum PieceType {
     EMPTY,
 }
 
-use std::collections::{HashMap, HashSet};
-use std::hash::{Hash, Hasher};
+use std::collections::HashSet;
 
 pub fn uniq_moves(list: Vec<Move>) -> Vec<Move> {
     list.into_iter()
@@ -41,39 +41,85 @@ impl fmt::Display for PieceType {
         }
     }
 }
-// x, y
-// The new position that a piece moves to
-#[derive(Clone, Copy, PartialOrd, Ord, Debug)]
-pub struct Move(pub i32, pub i32);
 
-impl Eq for Move {}
-impl fmt::Display for Move {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        println!("x: {}, y: {}", self.0, self.1);
-        Ok(())
-    }
+// The pieces a pawn can be promoted to
+pub const PROMOTION_PIECES: [PieceType; 4] = [
+    PieceType::QUEEN,
+    PieceType::ROOK,
+    PieceType::BISHOP,
+    PieceType::KNIGHT,
+];
+
+// What kind of move it is, special moves need more than just moving a piece
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MoveKind {
+    QUIET,
+    CAPTURE,
+    CASTLE,
+    ENPASSANT,
+    DOUBLEPUSH,
 }
-impl PartialEq for Move {
-    fn eq(&self, other: &Self) -> bool {
-        if self.0 == other.0 && self.1 == other.1 {
-            return true;
+
+// A complete move, from and to are squares on the board
+// promotion is the piece a pawn becomes when it reaches the last rank
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    pub promotion: Option<PieceType>,
+    pub kind: MoveKind,
+}
+
+impl Move {
+    pub fn new(from: Square, to: Square, kind: MoveKind) -> Move {
+        Move {
+            from,
+            to,
+            promotion: None,
+            kind,
+        }
+    }
+
+    pub fn with_promotion(self, piece_type: PieceType) -> Move {
+        Move {
+            promotion: Some(piece_type),
+            ..self
         }
-        return false;
     }
 }
-impl Hash for Move {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.0.hash(state);
-        self.1.hash(state);
+
+// The squares the piece moves from and to, like e2e4, and the promotion piece like e7e8q.
+// Game::san gives the notation with the piece and captures
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+        match self.promotion {
+            Some(PieceType::QUEEN) => write!(f, "q"),
+            Some(PieceType::ROOK) => write!(f, "r"),
+            Some(PieceType::BISHOP) => write!(f, "b"),
+            Some(PieceType::KNIGHT) => write!(f, "n"),
+            _ => Ok(()),
+        }
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum Color {
     WHITE,
     BLACK,
     EMPTY,
 }
+impl Color {
+    // The other side, EMPTY stays EMPTY
+    pub fn opposite(self) -> Color {
+        match self {
+            Color::WHITE => Color::BLACK,
+            Color::BLACK => Color::WHITE,
+            Color::EMPTY => Color::EMPTY,
+        }
+    }
+}
+
 impl fmt::Display for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -83,7 +129,7 @@ impl fmt::Display for Color {
         }
     }
 }
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct Piece {
     pub color: Color,
     pub piece_type: PieceType,
@@ -94,8 +140,6 @@ pub fn filter_illegal_moves(
     board: &Board,
     psuedo_legal_moves: Vec<Move>,
     color: Color,
-    x: i32,
-    y: i32,
 ) -> Vec<Move> {
     let mut legal_moves: Vec<Move> = Vec::new();
 
@@ -103,12 +147,11 @@ pub fn filter_illegal_moves(
     for piece_move in psuedo_legal_moves {
         // Maybe ineffictient to clone every time
         // But easy way to reset the board
-        if board.pieces[piece_move.1 as usize][piece_move.0 as usize].piece_type == PieceType::KING
-        {
+        if board[piece_move.to].piece_type == PieceType::KING {
             continue;
         }
-        let mut board_copy = board.clone();
-        simulate_piece_move(&mut board_copy, piece_move, x, y).ok();
+        let mut board_copy = *board;
+        simulate_piece_move(&mut board_copy, piece_move);
 
         // check if not in check after that specific move
         if !in_check(board_copy, color) {
@@ -120,375 +163,638 @@ pub fn filter_illegal_moves(
     return legal_moves;
 }
 
+// What makes a pseudo legal move illegal in a position, found once for all the pieces of a side
+// so the moves can be checked without playing them on a copy of the board
+struct Legality {
+    color: Color,
+    king: Option<Square>,
+    checkers: Bitboard,
+    pinned: Bitboard,
+    // The squares a piece can move to to stop a single check: the checker and the squares in between
+    evasions: Bitboard,
+    // The board without the king, so the king can not hide behind itself from a sliding piece
+    without_king: Board,
+}
+
+impl Legality {
+    fn new(board: &Board, color: Color) -> Legality {
+        let king = board.king_square(color);
+        let checkers = board.checkers(color);
+        let mut without_king = *board;
+        let mut evasions = checkers;
+        if let Some(king) = king {
+            without_king.set_piece(king, empty_piece());
+            for checker in squares(checkers) {
+                evasions |= between(king, checker);
+            }
+        }
+        return Legality {
+            color,
+            king,
+            checkers,
+            pinned: board.pinned(color),
+            evasions,
+            without_king,
+        };
+    }
+
+    // Castling and en passant are checked where they are generated
+    fn allows(&self, board: &Board, piece_move: Move) -> bool {
+        if board[piece_move.to].piece_type == PieceType::KING {
+            return false;
+        }
+        let Some(king) = self.king else {
+            return true;
+        };
+        if piece_move.from == king {
+            return piece_move.kind == MoveKind::CASTLE
+                || self
+                    .without_king
+                    .attackers(piece_move.to, self.color.opposite())
+                    == 0;
+        }
+        // Only the king can get out of a double check
+        if self.checkers.count_ones() > 1 {
+            return false;
+        }
+        if self.checkers != 0 && !contains(self.evasions, piece_move.to) {
+            return false;
+        }
+        // A pinned piece can only move along the line to its king
+        return !contains(self.pinned, piece_move.from)
+            || contains(line(king, piece_move.from), piece_move.to);
+    }
+}
+
 // Returns a tuple of boolean, first value if castlign to the right is possible, second value if castling to the left is possible
 // (right_possible, left_possible)
 pub fn castle_possible(board: &Board, color: Color) -> (bool, bool) {
+    return (
+        castle_blocked_reason(board, color, true).is_none(),
+        castle_blocked_reason(board, color, false).is_none(),
+    );
+}
+
+// Why the board does not allow color to castle to the right or the left side, None if it does.
+// Right and left are the order of Game::can_castle_white
+// Only the pieces on the board are looked at, the castling rights of the game are not
+pub fn castle_blocked_reason(board: &Board, color: Color, right: bool) -> Option<CastlingReason> {
+    let row = match color {
+        Color::WHITE => 7,
+        Color::BLACK => 0,
+        Color::EMPTY => return Some(CastlingReason::NORIGHTS),
+    };
+    let king_side = right == (color == Color::WHITE);
+    let rook_x = if king_side { 7 } else { 0 };
+
+    let piece_on_row = |x: i32| board[Square::new(x, row).expect("x is a file on the board")];
+    let king = piece_on_row(4);
+    let rook = piece_on_row(rook_x);
+    if king.piece_type != PieceType::KING
+        || king.color != color
+        || king.has_moved
+        || rook.piece_type != PieceType::ROOK
+        || rook.color != color
+        || rook.has_moved
+    {
+        return Some(CastlingReason::NORIGHTS);
+    }
     if in_check(*board, color) {
-        return (false, false);
+        return Some(CastlingReason::INCHECK);
     }
-    if color == Color::WHITE {
-        let mut result = (false, false);
-        let right_intermediate_positions = vec![(6, 7), (5, 7)];
-        let left_intermediate_positions = vec![(2, 7), (3, 7), (1, 7)];
-        let right_positions_in_check =
-            positions_in_check(*board, color, right_intermediate_positions);
-        let left_positions_in_check =
-            positions_in_check(*board, color, left_intermediate_positions);
-
-        let left_rook_pos = board.pieces[7][0];
-        let right_rook_pos = board.pieces[7][7];
-        let king_pos = board.pieces[7][4];
-        // check rook to the left
-        if !left_positions_in_check
-            && left_rook_pos.piece_type == PieceType::ROOK
-            && left_rook_pos.has_moved == false
-            && king_pos.piece_type == PieceType::KING
-            && board.pieces[7][1].piece_type == PieceType::EMPTY
-            && board.pieces[7][2].piece_type == PieceType::EMPTY
-            && board.pieces[7][3].piece_type == PieceType::EMPTY
-            && king_pos.piece_type == PieceType::KING
-            && king_pos.has_moved == false
-        {
-            result.1 = true;
-        }
-        // check rook to the right
-        if !right_positions_in_check
-            && right_rook_pos.piece_type == PieceType::ROOK
-            && right_rook_pos.has_moved == false
-            && board.pieces[7][6].piece_type == PieceType::EMPTY
-            && board.pieces[7][5].piece_type == PieceType::EMPTY
-            && king_pos.piece_type == PieceType::KING
-            && king_pos.has_moved == false
-        {
-            result.0 = true;
+    let between = if king_side { 5..7 } else { 1..4 };
+    if between
+        .into_iter()
+        .any(|x| piece_on_row(x).piece_type != PieceType::EMPTY)
+    {
+        return Some(CastlingReason::PATHBLOCKED);
+    }
+    // Only the positions the king moves over and to have to be safe, the b file does not matter
+    let king_path = if king_side { [5, 6] } else { [3, 2] };
+    let king_path = king_path
+        .into_iter()
+        .filter_map(|x| Square::new(x, row))
+        .collect();
+    if positions_in_check(*board, color, king_path) {
+        return Some(CastlingReason::ATTACKEDSQUARE);
+    }
+    return None;
+}
+
+// Turns the result of castle_possible into king moves
+pub fn castle_moves(board: &Board, color: Color) -> Vec<Move> {
+    let (right, left) = castle_possible(board, color);
+    let (row, right_dx) = match color {
+        Color::WHITE => (7, 2),
+        Color::BLACK => (0, -2),
+        Color::EMPTY => return vec![],
+    };
+    let Some(king) = Square::new(4, row) else {
+        return vec![];
+    };
+    return [(right, right_dx), (left, -right_dx)]
+        .into_iter()
+        .filter(|(possible, _dx)| *possible)
+        .filter_map(|(_possible, dx)| king.offset(dx, 0))
+        .map(|to| Move::new(king, to, MoveKind::CASTLE))
+        .collect();
+}
+
+// get_legal_moves -> filter_illegal_moves -> in_check -> get_legal_moves (recursive infinite loop, bad)
+
+// Applies the move to the board without checking if it is legal
+// Takes care of moving the rook when castling, removing the captured pawn in en passant and promotions
+pub fn simulate_piece_move(board: &mut Board, piece_move: Move) {
+    let mut piece = board[piece_move.from];
+    piece.has_moved = true;
+    if let Some(piece_type) = piece_move.promotion {
+        piece.piece_type = piece_type;
+    }
+    board.set_piece(piece_move.to, piece);
+    board.set_piece(piece_move.from, empty_piece());
+
+    if piece_move.kind == MoveKind::ENPASSANT {
+        // The captured pawn stands beside the pawn that moved
+        board.set_piece(en_passant_capture_square(piece_move), empty_piece());
+    }
+    if piece_move.kind == MoveKind::CASTLE {
+        let (rook_from, rook_to) = castle_rook_positions(piece_move);
+        let mut rook = board[rook_from];
+        rook.has_moved = true;
+        board.set_piece(rook_to, rook);
+        board.set_piece(rook_from, empty_piece());
+    }
+}
+
+// Takes back a move applied with simulate_piece_move, the record holds what the move overwrote
+pub fn unsimulate_piece_move(board: &mut Board, record: &MoveRecord) {
+    let piece_move = record.piece_move;
+
+    board.set_piece(piece_move.from, record.moved_piece);
+    board.set_piece(piece_move.to, empty_piece());
+
+    match piece_move.kind {
+        MoveKind::ENPASSANT => {
+            board.set_piece(en_passant_capture_square(piece_move), record.captured_piece);
         }
-        return result;
-    } else if color == Color::BLACK {
-        let mut result = (false, false);
-        let left_intermediate_positions = vec![(6, 0), (5, 0)];
-        let right_intermediate_positions = vec![(2, 0), (3, 0), (1, 0)];
-        let right_positions_in_check =
-            positions_in_check(*board, color, right_intermediate_positions);
-        let left_positions_in_check =
-            positions_in_check(*board, color, left_intermediate_positions);
-
-        let left_rook_pos = board.pieces[0][7];
-        let right_rook_pos = board.pieces[0][0];
-        let king_pos = board.pieces[0][4];
-        // check rook to the left
-        if !left_positions_in_check
-            && left_rook_pos.piece_type == PieceType::ROOK
-            && left_rook_pos.has_moved == false
-            && king_pos.piece_type == PieceType::KING
-            && board.pieces[0][6].piece_type == PieceType::EMPTY
-            && board.pieces[0][5].piece_type == PieceType::EMPTY
-            && king_pos.piece_type == PieceType::KING
-            && king_pos.has_moved == false
-        {
-            result.1 = true;
+        MoveKind::CASTLE => {
+            // The rook can not have moved before castling
+            let (rook_from, rook_to) = castle_rook_positions(piece_move);
+            let mut rook = board[rook_to];
+            rook.has_moved = false;
+            board.set_piece(rook_from, rook);
+            board.set_piece(rook_to, empty_piece());
         }
-        // check rook to the right
-        if !right_positions_in_check
-            && right_rook_pos.piece_type == PieceType::ROOK
-            && right_rook_pos.has_moved == false
-            && board.pieces[0][1].piece_type == PieceType::EMPTY
-            && board.pieces[0][2].piece_type == PieceType::EMPTY
-            && board.pieces[0][3].piece_type == PieceType::EMPTY
-            && king_pos.piece_type == PieceType::KING
-            && king_pos.has_moved == false
-        {
-            result.0 = true;
+        _ => {
+            board.set_piece(piece_move.to, record.captured_piece);
         }
-        return result;
     }
-    return (false, false);
 }
 
-// get_legal_moves -> filter_illegal_moves -> in_check -> get_legal_moves (recursive infinite loop, bad)
+// Where the rook moves from and to when castling, (from, to)
+fn castle_rook_positions(castle_move: Move) -> (Square, Square) {
+    let (from_x, to_x) = if castle_move.to.x() == 6 {
+        (7, 5)
+    } else {
+        (0, 3)
+    };
+    let y = castle_move.from.y();
+    let square = |x| Square::new(x, y).expect("the rook stays on the row of the king");
+    return (square(from_x), square(to_x));
+}
 
-fn simulate_piece_move(board: &mut Board, piece_move: Move, x: i32, y: i32) -> Result<(), &str> {
-    if x > 7 || y > 7 || x < 0 || x < 0 {
-        return Err("Invalid move variable");
-    }
+// The captured pawn in en passant stands beside the pawn that moved, on the file it moves to
+fn en_passant_capture_square(piece_move: Move) -> Square {
+    return Square::new(piece_move.to.x(), piece_move.from.y())
+        .expect("the file and row come from squares on the board");
+}
 
-    let piece = board.pieces[y as usize][x as usize];
-    board.pieces[piece_move.1 as usize][piece_move.0 as usize] = piece;
-    board.pieces[y as usize][x as usize] = Piece {
+fn empty_piece() -> Piece {
+    Piece {
         color: Color::EMPTY,
         piece_type: PieceType::EMPTY,
         has_moved: false,
-    };
-
-    return Ok(());
+    }
 }
 
-pub fn get_pseudo_legal_moves(board: Board, x: i32, y: i32, color: Color) -> Vec<Move> {
-    let piece = board.pieces[y as usize][x as usize];
+pub fn get_pseudo_legal_moves(board: Board, square: Square, _color: Color) -> Vec<Move> {
+    let piece = board[square];
 
     let moves = match piece.piece_type {
-        PieceType::BISHOP => bishop_legal_moves(
-            x.try_into().unwrap(),
-            y.try_into().unwrap(),
-            board,
-            piece.color,
-        ),
+        PieceType::BISHOP => bishop_legal_moves(square, board, piece.color),
         PieceType::EMPTY => vec![],
-        PieceType::KING => king_legal_moves(
-            x.try_into().unwrap(),
-            y.try_into().unwrap(),
-            board,
-            piece.color,
-        ),
-        PieceType::KNIGHT => knight_legal_moves(
-            x.try_into().unwrap(),
-            y.try_into().unwrap(),
-            board,
-            piece.color,
-        ),
-        PieceType::PAWN => pawn_legal_moves(
-            x.try_into().unwrap(),
-            y.try_into().unwrap(),
-            &board,
-            piece.color,
-        ),
-        PieceType::QUEEN => queen_legal_moves(
-            x.try_into().unwrap(),
-            y.try_into().unwrap(),
-            board,
-            piece.color,
-        ),
-        PieceType::ROOK => rook_legal_moves(
-            x.try_into().unwrap(),
-            y.try_into().unwrap(),
-            board,
-            piece.color,
-        ),
+        PieceType::KING => king_legal_moves(square, board, piece.color),
+        PieceType::KNIGHT => knight_legal_moves(square, board, piece.color),
+        PieceType::PAWN => pawn_legal_moves(square, &board, piece.color),
+        PieceType::QUEEN => queen_legal_moves(square, board, piece.color),
+        PieceType::ROOK => rook_legal_moves(square, board, piece.color),
     };
     return moves;
 }
-// Given a position, return the next valid positions
-pub fn get_legal_moves(board: Board, x: i32, y: i32, color: Color) -> Vec<Move> {
-    let piece: Piece = board.pieces[y as usize][x as usize];
+// Given a square, return the next valid squares
+// En passant depends on the previous move so it is only included in Game::legal_moves_from
+pub fn get_legal_moves(board: Board, square: Square, _color: Color) -> Vec<Move> {
+    let legality = Legality::new(&board, board[square].color);
+    return legal_piece_moves(&board, square, &legality);
+}
 
-    let moves = match piece.piece_type {
-        PieceType::BISHOP => bishop_legal_moves(
-            x.try_into().unwrap(),
-            y.try_into().unwrap(),
-            board,
-            piece.color,
-        ),
-        PieceType::EMPTY => vec![],
-        PieceType::KING => king_legal_moves(
-            x.try_into().unwrap(),
-            y.try_into().unwrap(),
-            board,
-            piece.color,
-        ),
-        PieceType::KNIGHT => knight_legal_moves(
-            x.try_into().unwrap(),
-            y.try_into().unwrap(),
-            board,
-            piece.color,
-        ),
-        PieceType::PAWN => pawn_legal_moves(
-            x.try_into().unwrap(),
-            y.try_into().unwrap(),
-            &board,
-            piece.color,
-        ),
-        PieceType::QUEEN => queen_legal_moves(
-            x.try_into().unwrap(),
-            y.try_into().unwrap(),
-            board,
-            piece.color,
-        ),
-        PieceType::ROOK => rook_legal_moves(
-            x.try_into().unwrap(),
-            y.try_into().unwrap(),
-            board,
-            piece.color,
-        ),
+fn legal_piece_moves(board: &Board, square: Square, legality: &Legality) -> Vec<Move> {
+    let piece: Piece = board[square];
+
+    let mut moves = get_pseudo_legal_moves(*board, square, piece.color);
+    if piece.piece_type == PieceType::KING {
+        moves.extend(
+            castle_moves(board, piece.color)
+                .into_iter()
+                .filter(|castle_move| castle_move.from == square),
+        );
+    }
+    moves.retain(|piece_move| legality.allows(board, *piece_move));
+    return moves;
+}
+
+// All the legal moves for the piece on the square in the game, including en passant
+// and only the castling moves the castling rights allow
+pub fn legal_moves_from(game: &Game, square: Square) -> Vec<Move> {
+    let legality = Legality::new(&game.board, game.board[square].color);
+    return legal_game_moves(game, square, &legality);
+}
+
+fn legal_game_moves(game: &Game, square: Square, legality: &Legality) -> Vec<Move> {
+    let mut moves = legal_piece_moves(&game.board, square, legality);
+    moves.retain(|piece_move| {
+        piece_move.kind != MoveKind::CASTLE || castle_allowed(game, *piece_move)
+    });
+    moves.extend(en_passant_moves(game, square));
+    return moves;
+}
+
+// Builds a move between the squares, a capture if there is a piece on the new square
+fn step_move(board: &Board, from: Square, to: Square) -> Move {
+    let kind = if board[to].piece_type == PieceType::EMPTY {
+        MoveKind::QUIET
+    } else {
+        MoveKind::CAPTURE
     };
-    return filter_illegal_moves(&board, moves, piece.color, x, y);
+    return Move::new(from, to, kind);
 }
 
-pub fn can_pawn_promote(board: &Board, color: Color) -> Option<(i32, i32)> {
-    if color == Color::WHITE {
-        for x in 0..8 {
-            if board.pieces[0][x].piece_type == PieceType::PAWN
-                && board.pieces[0][x].color == Color::WHITE
-            {
-                return Some((x as i32, 0));
-            }
+// A pawn that reaches the last rank has to be promoted, so add one move per promotion piece
+fn push_pawn_move(moves: &mut Vec<Move>, pawn_move: Move) {
+    if pawn_move.to.y() == 0 || pawn_move.to.y() == 7 {
+        for piece_type in PROMOTION_PIECES {
+            moves.push(pawn_move.with_promotion(piece_type));
         }
-        None
     } else {
-        for x in 0..8 {
-            if board.pieces[7][x].piece_type == PieceType::PAWN
-                && board.pieces[7][x].color == Color::BLACK
-            {
-                return Some((x as i32, 7));
-            }
-        }
-        None
+        moves.push(pawn_move);
     }
 }
 
-pub fn check_en_passant(game: &Game, color: Color, x: i32, y: i32) -> Option<(i32, i32)> {
-    if color == Color::WHITE {
-        // check if pawn is beside white pawn
-        if game.black_moves[0].1 == PieceType::PAWN
-            && ((x > 0
-                && game.board.pieces[3][x as usize - 1].piece_type == PieceType::PAWN
-                && game.board.pieces[3][x as usize - 1].color == Color::WHITE)
-                || (x < 7
-                    && game.board.pieces[3][x as usize + 1].piece_type == PieceType::PAWN
-                    && game.board.pieces[3][x as usize + 1].color == Color::WHITE))
-            && game.black_moves[0].0 .1 == 3
-        {
-            return Some((x, y));
-        }
-    } else if color == Color::BLACK {
-        if game.white_moves[0].1 == PieceType::PAWN
-            && ((x > 0
-                && game.board.pieces[4][x as usize - 1].piece_type == PieceType::PAWN
-                && game.board.pieces[4][x as usize - 1].color == Color::BLACK)
-                || (x < 7
-                    && game.board.pieces[4][x as usize + 1].piece_type == PieceType::PAWN
-                    && game.board.pieces[4][x as usize + 1].color == Color::BLACK))
-            && game.white_moves[0].0 .1 == 4
-        {
-            return Some((x, y));
-        }
+// The castling rights a side has in a position where nothing is known about earlier moves,
+// a side can castle as long as the king and rook have not left their starting positions
+// (right, left) like castle_possible
+pub fn initial_castling_rights(board: &Board, color: Color) -> (bool, bool) {
+    let (row, right_rook, left_rook) = match color {
+        Color::WHITE => (7, 7, 0),
+        Color::BLACK => (0, 0, 7),
+        Color::EMPTY => return (false, false),
+    };
+    let unmoved = |x: i32, piece_type: PieceType| {
+        let piece = board[Square::new(x, row).expect("x is a file on the board")];
+        piece.piece_type == piece_type && piece.color == color && !piece.has_moved
+    };
+    if !unmoved(4, PieceType::KING) {
+        return (false, false);
     }
-    return None;
+    return (
+        unmoved(right_rook, PieceType::ROOK),
+        unmoved(left_rook, PieceType::ROOK),
+    );
 }
 
-pub fn en_passant_move(game: &mut Game, color: Color, x: i32, y: i32) {
-    if color == Color::WHITE {
-        if game.white_en_passant.is_some() {
-            simulate_piece_move(&mut game.board, Move(x - 1, y - 1), x, y).ok();
-            // Remove the captured pawn
-            game.board.pieces[(y) as usize][game.black_moves[0].0 .0 as usize] = Piece {
-                color: Color::EMPTY,
-                piece_type: PieceType::EMPTY,
-                has_moved: false,
-            };
-            game.white_captures.push(PieceType::PAWN);
-            game.white_en_passant = None;
-        }
-    } else if color == Color::BLACK {
-        if game.black_en_passant.is_some() {
-            simulate_piece_move(&mut game.board, Move(x, y + 1), x, y + 1).ok();
-            // Remove the captured pawn
-            game.board.pieces[(y + 1) as usize][game.white_moves[0].0 .0 as usize] = Piece {
-                color: Color::EMPTY,
-                piece_type: PieceType::EMPTY,
-                has_moved: false,
-            };
-            game.black_captures.push(PieceType::PAWN);
-            game.black_en_passant = None;
+// A side loses the right to castle when the king moves or when the rook leaves or is captured on its starting position
+fn update_castling_rights(game: &mut Game, piece_move: Move) {
+    for square in [piece_move.from, piece_move.to] {
+        match (square.x(), square.y()) {
+            (4, 7) => game.can_castle_white = (false, false),
+            (7, 7) => game.can_castle_white.0 = false,
+            (0, 7) => game.can_castle_white.1 = false,
+            (4, 0) => game.can_castle_black = (false, false),
+            (0, 0) => game.can_castle_black.0 = false,
+            (7, 0) => game.can_castle_black.1 = false,
+            _ => {}
         }
     }
 }
 
-// dir = -1 -> left, dir = 1 -> right
-pub fn make_castle_move(game: &mut Game, color: Color, dir: i32) -> Result<(), &'static str> {
-    if color == Color::WHITE {
-        if game.can_castle_white.1 && dir == -1 {
-            simulate_piece_move(&mut game.board, Move(2, 7), 4, 7).ok();
-            simulate_piece_move(&mut game.board, Move(3, 7), 0, 7).ok();
-            return Ok(());
-        }
-        if game.can_castle_white.0 && dir == 1 {
-            simulate_piece_move(&mut game.board, Move(6, 7), 4, 7).ok();
-            simulate_piece_move(&mut game.board, Move(5, 7), 7, 7).ok();
-            return Ok(());
-        }
-        return Err("Could not castle");
-    } else if color == Color::BLACK {
-        if game.can_castle_black.1 && dir == -1 {
-            simulate_piece_move(&mut game.board, Move(2, 0), 4, 0).ok();
-            simulate_piece_move(&mut game.board, Move(3, 0), 0, 0).ok();
-            return Ok(());
-        }
-        if game.can_castle_black.0 && dir == 1 {
-            simulate_piece_move(&mut game.board, Move(6, 0), 4, 0).ok();
-            simulate_piece_move(&mut game.board, Move(5, 0), 7, 0).ok();
-            return Ok(());
+// If the castling move is still allowed by the castling rights of the game
+pub fn castle_allowed(game: &Game, castle_move: Move) -> bool {
+    match (castle_move.from.y(), castle_move.to.x()) {
+        (7, 6) => game.can_castle_white.0,
+        (7, 2) => game.can_castle_white.1,
+        (0, 2) => game.can_castle_black.0,
+        (0, 6) => game.can_castle_black.1,
+        _ => false,
+    }
+}
+
+// After a double push the en passant target is the square the pawn skipped over
+pub fn en_passant_target(piece_move: Move) -> Option<Square> {
+    if piece_move.kind != MoveKind::DOUBLEPUSH {
+        return None;
+    }
+    let to = piece_move.to;
+    return Square::new(to.x(), (piece_move.from.y() + to.y()) / 2);
+}
+
+// The en passant captures the piece on the square can make
+pub fn en_passant_moves(game: &Game, square: Square) -> Vec<Move> {
+    let piece = game.board[square];
+    return filter_illegal_moves(
+        &game.board,
+        pseudo_legal_en_passant_moves(game, square),
+        piece.color,
+    );
+}
+
+// En passant captures without checking if they leave the king in check
+fn pseudo_legal_en_passant_moves(game: &Game, square: Square) -> Vec<Move> {
+    let piece = game.board[square];
+    if piece.piece_type != PieceType::PAWN {
+        return vec![];
+    }
+    // White captures on the third row from the top, black on the sixth
+    let (dir, target_row) = match piece.color {
+        Color::WHITE => (-1, 2),
+        Color::BLACK => (1, 5),
+        Color::EMPTY => return vec![],
+    };
+    match game.en_passant {
+        Some(target)
+            if target.y() == target_row
+                && square.y() + dir == target.y()
+                && (target.x() - square.x()).abs() == 1 =>
+        {
+            vec![Move::new(square, target, MoveKind::ENPASSANT)]
         }
-        return Err("Could not castle");
+        _ => vec![],
     }
-    return Err("Could not castle");
 }
 
-pub fn move_piece(piece_move: Move, x: i32, y: i32, game: &mut Game) -> Result<(), &'static str> {
-    if x > 7 || y > 7 || x < 0 || x < 0 {
-        return Err("Invalid move variable");
+// The piece type the move captures, the captured pawn in en passant is not on the square the pawn moves to
+pub fn captured_piece(board: &Board, piece_move: Move) -> PieceType {
+    if piece_move.kind == MoveKind::ENPASSANT {
+        return PieceType::PAWN;
     }
+    return board[piece_move.to].piece_type;
+}
 
-    let mut piece = game.board.pieces[y as usize][x as usize];
+// Why the piece on from can not move to to, None if the move is legal.
+// Checked in the order a player would run into them: the position, the turn,
+// the way the piece moves, pieces in the way and finally the safety of the king
+pub fn illegal_move_reason(game: &Game, from: Square, to: Square) -> Option<ChessError> {
+    if game.game_over() {
+        return Some(ChessError::GAMEOVER);
+    }
+    let piece = game.board[from];
+    if piece.color == Color::EMPTY {
+        return Some(ChessError::NOPIECE(from));
+    }
     if piece.color != game.turn {
-        return Err("Not your piece");
+        return Some(ChessError::NOTYOURTURN(game.turn));
     }
-    let legal_moves = get_legal_moves(game.board, x, y, piece.color);
+    if game
+        .legal_moves_from(from)
+        .iter()
+        .any(|legal_move| legal_move.to == to)
+    {
+        return None;
+    }
+
+    // Only the king on its starting square moving to the c or g file tries to castle
+    let home_row = if piece.color == Color::WHITE { 7 } else { 0 };
+    if piece.piece_type == PieceType::KING
+        && (from.x(), from.y()) == (4, home_row)
+        && to.y() == home_row
+        && (to.x() == 2 || to.x() == 6)
+    {
+        return Some(ChessError::CASTLINGNOTALLOWED(castling_reason(
+            game,
+            Move::new(from, to, MoveKind::CASTLE),
+        )));
+    }
+    let reason = if game.board[to].color == piece.color {
+        IllegalMoveReason::OCCUPIED
+    } else if reaches_position(game, from, to) {
+        // The move only fails because of the king
+        if piece.piece_type == PieceType::KING {
+            IllegalMoveReason::KINGINTOCHECK
+        } else if game.board.checkers(piece.color) == 0
+            && contains(game.board.pinned(piece.color), from)
+        {
+            IllegalMoveReason::PINNED
+        } else {
+            IllegalMoveReason::KINGINCHECK
+        }
+    } else if is_en_passant_shape(game, from, to) {
+        IllegalMoveReason::ENPASSANTUNAVAILABLE
+    } else if reaches_on_empty_board(piece, from, to) {
+        IllegalMoveReason::PATHBLOCKED
+    } else {
+        IllegalMoveReason::UNREACHABLE
+    };
+    return Some(ChessError::ILLEGALMOVE(reason));
+}
 
-    // Check if it is actually is a legal move
-    if !legal_moves.contains(&piece_move) {
-        return Err("Illegal move");
+// If the piece can move to the square when the safety of its king is not looked at
+fn reaches_position(game: &Game, from: Square, to: Square) -> bool {
+    let color = game.board[from].color;
+    let mut pseudo_legal_moves = get_pseudo_legal_moves(game.board, from, color);
+    pseudo_legal_moves.extend(pseudo_legal_en_passant_moves(game, from));
+    return pseudo_legal_moves
+        .iter()
+        .any(|pseudo_legal_move| pseudo_legal_move.to == to);
+}
+
+// If the piece could move to the square if it was alone on the board
+fn reaches_on_empty_board(piece: Piece, from: Square, to: Square) -> bool {
+    let mut board = Board::empty();
+    board.set_piece(from, piece);
+    return get_pseudo_legal_moves(board, from, piece.color)
+        .iter()
+        .any(|pseudo_legal_move| pseudo_legal_move.to == to);
+}
+
+// A pawn capturing diagonally onto an empty square beside an enemy pawn, which is only
+// allowed right after that pawn moved two squares
+fn is_en_passant_shape(game: &Game, from: Square, to: Square) -> bool {
+    let piece = game.board[from];
+    let (dir, pawn_row) = match piece.color {
+        Color::WHITE => (-1, 3),
+        Color::BLACK => (1, 4),
+        Color::EMPTY => return false,
+    };
+    let beside = game.board[en_passant_capture_square(Move::new(from, to, MoveKind::ENPASSANT))];
+    return piece.piece_type == PieceType::PAWN
+        && from.y() == pawn_row
+        && to.y() == from.y() + dir
+        && (to.x() - from.x()).abs() == 1
+        && game.board[to].piece_type == PieceType::EMPTY
+        && beside.piece_type == PieceType::PAWN
+        && beside.color != piece.color;
+}
+
+// Why the king can not castle to the position the move goes to
+fn castling_reason(game: &Game, castle_move: Move) -> CastlingReason {
+    let color = game.board[castle_move.from].color;
+    if castle_move.from.x() != 4 || !castle_allowed(game, castle_move) {
+        return CastlingReason::NORIGHTS;
     }
-    let possible_capture =
-        game.board.pieces[piece_move.1 as usize][piece_move.0 as usize].piece_type;
-    piece.has_moved = true;
+    let right = (castle_move.to.x() == 6) == (color == Color::WHITE);
+    return castle_blocked_reason(&game.board, color, right).unwrap_or(CastlingReason::NORIGHTS);
+}
 
-    game.board.pieces[piece_move.1 as usize][piece_move.0 as usize] = piece;
-    game.board.pieces[y as usize][x as usize] = Piece {
-        color: Color::EMPTY,
-        piece_type: PieceType::EMPTY,
-        has_moved: false,
+// The single entry point for playing a move, castling, en passant and promotions included.
+// The move has to be one of the moves from Game::legal_moves_from,
+// only from, to and promotion are compared so the kind is taken from the generated move.
+// Everything is checked before the board is touched, so the game is never left half updated
+pub fn move_piece(piece_move: Move, game: &mut Game) -> Result<(), ChessError> {
+    let legal_move = find_legal_move(game, piece_move)?;
+    play_legal_move(game, legal_move);
+    return Ok(());
+}
+
+// The legal move with the same from, to and promotion as the move, or why there is none
+pub fn find_legal_move(game: &Game, piece_move: Move) -> Result<Move, ChessError> {
+    if let Some(error) = illegal_move_reason(game, piece_move.from, piece_move.to) {
+        return Err(error);
+    }
+    let legal_move = game
+        .legal_moves_from(piece_move.from)
+        .into_iter()
+        .find(|legal_move| {
+            legal_move.to == piece_move.to && legal_move.promotion == piece_move.promotion
+        });
+
+    // The position can be reached, so only the promotion piece can be wrong
+    let Some(legal_move) = legal_move else {
+        if piece_move.promotion.is_none() {
+            return Err(ChessError::PROMOTIONPENDING);
+        }
+        return Err(ChessError::ILLEGALMOVE(IllegalMoveReason::NOPROMOTION));
     };
+    return Ok(legal_move);
+}
+
+// Plays a move from Game::legal_moves and updates the game, without checking anything first.
+// Used by move_piece after the checks and by perft, which has to play on after the game is over
+pub fn play_legal_move(game: &mut Game, legal_move: Move) {
+    let piece = game.board[legal_move.from];
+    let possible_capture = captured_piece(&game.board, legal_move);
+    let record = MoveRecord {
+        piece_move: legal_move,
+        moved_piece: piece,
+        captured_piece: if legal_move.kind == MoveKind::ENPASSANT {
+            game.board[en_passant_capture_square(legal_move)]
+        } else {
+            game.board[legal_move.to]
+        },
+        can_castle_white: game.can_castle_white,
+        can_castle_black: game.can_castle_black,
+        en_passant: game.en_passant,
+        white_in_check: game.white_in_check,
+        black_in_check: game.black_in_check,
+        check_mate_white: game.check_mate_white,
+        check_mate_black: game.check_mate_black,
+        stalemate: game.stalemate,
+        halfmove_clock: game.halfmove_clock,
+        fullmove_number: game.fullmove_number,
+    };
+
+    simulate_piece_move(&mut game.board, legal_move);
+    update_castling_rights(game, legal_move);
     update_game_state(
-        game.board,
         game,
         piece.color,
         piece.piece_type,
-        piece_move,
+        legal_move,
         possible_capture,
     );
-    return Ok(());
+    game.history.push(record);
+    // A new move makes the taken back moves invalid
+    game.undone.clear();
+}
+
+// Takes back the last move of the game and puts it on the redo stack
+pub fn undo_piece_move(game: &mut Game) -> Option<Move> {
+    let record = game.history.pop()?;
+    unsimulate_piece_move(&mut game.board, &record);
+    // Taking back a move also takes back a resignation or an agreed draw
+    game.ended = None;
+
+    game.turn = record.moved_piece.color;
+    if record.moved_piece.color == Color::WHITE {
+        game.white_moves.remove(0);
+    } else {
+        game.black_moves.remove(0);
+    }
+    if record.captured_piece.piece_type != PieceType::EMPTY {
+        if record.moved_piece.color == Color::WHITE {
+            game.black_captures.pop();
+        } else {
+            game.white_captures.pop();
+        }
+    }
+
+    game.can_castle_white = record.can_castle_white;
+    game.can_castle_black = record.can_castle_black;
+    game.en_passant = record.en_passant;
+    game.white_in_check = record.white_in_check;
+    game.black_in_check = record.black_in_check;
+    game.check_mate_white = record.check_mate_white;
+    game.check_mate_black = record.check_mate_black;
+    game.stalemate = record.stalemate;
+    game.halfmove_clock = record.halfmove_clock;
+    game.fullmove_number = record.fullmove_number;
+    game.positions.pop();
+
+    game.undone.push(record);
+    return Some(record.piece_move);
+}
+
+// Plays the last taken back move again
+pub fn redo_piece_move(game: &mut Game) -> Option<Move> {
+    let record = game.undone.pop()?;
+    // move_piece clears the redo stack, keep the rest of it
+    let undone = std::mem::take(&mut game.undone);
+    let result = move_piece(record.piece_move, game);
+    game.undone = undone;
+    if result.is_err() {
+        game.undone.push(record);
+        return None;
+    }
+    return Some(record.piece_move);
+}
+
+// Updates if the sides are in check, check mate or stalemate in the current position
+pub fn update_check_state(game: &mut Game) {
+    game.white_in_check = in_check(game.board, Color::WHITE);
+    game.black_in_check = in_check(game.board, Color::BLACK);
+
+    // Needs the en passant state of the position since it can be the only way out of check
+    game.check_mate_black = in_check_mate(game, Color::BLACK);
+    game.check_mate_white = in_check_mate(game, Color::WHITE);
+    // Only the side that moves next can be out of moves
+    game.stalemate = in_stalemate(game, game.turn);
 }
 
 pub fn update_game_state(
-    board: Board,
     game: &mut Game,
     moved_color: Color,
     moved_piece: PieceType,
     piece_move: Move,
     captured_piece: PieceType,
 ) {
-    game.white_pawn_promotion = can_pawn_promote(&board, Color::WHITE);
-    game.black_pawn_promotion = can_pawn_promote(&board, Color::BLACK);
-
-    game.can_castle_black = castle_possible(&board, Color::BLACK);
-    game.can_castle_white = castle_possible(&board, Color::WHITE);
-
-    game.white_in_check = in_check(board, Color::WHITE);
-    game.black_in_check = in_check(board, Color::BLACK);
-
-    game.check_mate_black = in_check_mate(game, Color::BLACK);
-    game.check_mate_white = in_check_mate(game, Color::WHITE);
-
-    if game.white_in_check {
-        game.white_repetitions += 1;
-    } else {
-        game.white_repetitions = 0;
-    }
-    if game.black_in_check {
-        game.black_repetitions += 1;
-    } else {
-        game.black_repetitions = 0;
-    }
-
     if moved_color == Color::WHITE {
         game.turn = Color::BLACK;
         game.white_moves.insert(0, (piece_move, moved_piece));
@@ -504,345 +810,97 @@ pub fn update_game_state(
             game.white_captures.push(captured_piece);
         }
     }
-    if moved_color == Color::BLACK {
-        game.white_en_passant = check_en_passant(game, Color::WHITE, piece_move.0, piece_move.1);
-    } else if moved_color == Color::WHITE {
-        game.black_en_passant = check_en_passant(game, Color::BLACK, piece_move.0, piece_move.1);
-    }
-}
+    game.en_passant = en_passant_target(piece_move);
 
-pub fn promote_pawn(
-    game: &mut Game,
-    new_piece: PieceType,
-    color: Color,
-) -> Result<(), &'static str> {
-    if color == Color::WHITE && game.white_pawn_promotion.is_some() {
-        let (x, y) = game.white_pawn_promotion.unwrap();
-        game.board.pieces[y as usize][x as usize] = Piece {
-            color: Color::WHITE,
-            piece_type: new_piece,
-            has_moved: false,
-        };
-        game.white_pawn_promotion = None;
-        Ok(())
-    } else if color == Color::BLACK && game.black_pawn_promotion.is_some() {
-        let (x, y) = game.black_pawn_promotion.unwrap();
-        game.board.pieces[y as usize][x as usize] = Piece {
-            color: Color::BLACK,
-            piece_type: new_piece,
-            has_moved: false,
-        };
-        game.black_pawn_promotion = None;
-        Ok(())
+    // Pawn moves and captures can not be undone, so they reset the clock
+    if moved_piece == PieceType::PAWN || captured_piece != PieceType::EMPTY {
+        game.halfmove_clock = 0;
     } else {
-        return Err("No pawn to promote");
+        game.halfmove_clock += 1;
     }
-}
-
-pub fn bishop_legal_moves(x: i32, y: i32, board: Board, color: Color) -> Vec<Move> {
-    let mut valid_moves: Vec<Move> = Vec::new();
-    // Go row by row and check where bishop can go, if no legal moves in the next row then break out of loops
-
-    // 1. go right up, loop over all rows i.e 8 rows
-    for i in (y + 1)..8 {
-        let col: i32 = x + (y - i);
-        if col > 7 || col < 0 {
-            break;
-        }
-        // check if anything is to the right up
-        let piece = board.pieces[i as usize][col as usize];
-        if col >= 0 && col <= 7 && piece.color == Color::EMPTY {
-            valid_moves.push(Move(col, i));
-        } else if piece.color != color {
-            valid_moves.push(Move(col, i));
-            break;
-        } else {
-            break;
-        }
-    }
-    // 2. go right down
-    for i in (0..y).rev() {
-        let col = x + (y - i);
-        if col > 7 || col < 0 {
-            break;
-        }
-        // check if anything is to the right up
-        let piece = board.pieces[i as usize][col as usize];
-        if col <= 7 && col >= 0 && piece.color == Color::EMPTY {
-            valid_moves.push(Move(col, i));
-        } else if piece.color != color {
-            valid_moves.push(Move(col, i));
-            break;
-        } else {
-            break;
-        }
+    if moved_color == Color::BLACK {
+        game.fullmove_number += 1;
     }
 
-    // 3. go left up
-    for i in (y + 1)..8 {
-        let col: i32 = x + (i - y);
-        if col > 7 || col < 0 {
-            break;
-        }
-
-        // check if anything is to the left up
-        let piece = board.pieces[i as usize][col as usize];
-        if col >= 0 && col <= 7 && piece.color == Color::EMPTY {
-            valid_moves.push(Move(col, i));
-            // add valid move
-        } else if piece.color != color {
-            valid_moves.push(Move(col, i));
-            break;
-        } else {
-            break;
-        }
-    }
+    update_check_state(game);
 
-    // 4. go left down
-    for i in (0..y).rev() {
-        // problem when y = 7
-        let col: i32 = x + (i - y);
-        if col > 7 || col < 0 {
-            break;
-        }
-        // check if anything is to the right up
-        let piece = board.pieces[i as usize][col as usize];
-        if col >= 0 && col <= 7 && piece.color == Color::EMPTY {
-            valid_moves.push(Move(col, i));
-        } else if piece.color != color {
-            valid_moves.push(Move(col, i));
-            break;
-        } else {
-            break;
-        }
-    }
-    return valid_moves;
+    let position = game.zobrist_key();
+    game.positions.push(position);
 }
 
-pub fn pawn_legal_moves(x: i32, y: i32, board: &Board, color: Color) -> Vec<Move> {
-    let mut valid_moves: Vec<Move> = Vec::new();
-    let piece = board.pieces[y as usize][x as usize];
-    if color == Color::BLACK {
-        if y < 7
-            && board.pieces[(y + 1) as usize][x as usize].color != color
-            && board.pieces[(y + 1) as usize][x as usize].piece_type == PieceType::EMPTY
-        {
-            valid_moves.push(Move(x, y + 1));
-            if !piece.has_moved
-                && y < 6
-                && board.pieces[(y + 2) as usize][x as usize].color != color
-                && board.pieces[(y + 2) as usize][x as usize].piece_type == PieceType::EMPTY
-            {
-                valid_moves.push(Move(x, y + 2));
-            }
-        }
-        if x > 0
-            && y < 7
-            && board.pieces[(y + 1) as usize][(x - 1) as usize].color != color
-            && board.pieces[(y + 1) as usize][(x - 1) as usize].piece_type != PieceType::EMPTY
-        {
-            valid_moves.push(Move(x - 1, y + 1));
-        }
-        if x < 7
-            && y < 7
-            && board.pieces[(y + 1) as usize][(x + 1) as usize].color != color
-            && board.pieces[(y + 1) as usize][(x + 1) as usize].piece_type != PieceType::EMPTY
-        {
-            valid_moves.push(Move(x + 1, y + 1));
-        }
-    } else if color == Color::WHITE {
-        if y > 0
-            && board.pieces[(y - 1) as usize][x as usize].color != color
-            && board.pieces[(y - 1) as usize][x as usize].piece_type == PieceType::EMPTY
-        {
-            valid_moves.push(Move(x, y - 1));
-            if !piece.has_moved
-                && y > 1
-                && board.pieces[(y - 2) as usize][x as usize].color != color
-                && board.pieces[(y - 2) as usize][x as usize].piece_type == PieceType::EMPTY
-            {
-                valid_moves.push(Move(x, y - 2));
-            }
-        }
-        if x > 0
-            && y > 0
-            && board.pieces[(y - 1) as usize][(x - 1) as usize].color != color
-            && board.pieces[(y - 1) as usize][(x - 1) as usize].piece_type != PieceType::EMPTY
-        {
-            valid_moves.push(Move(x - 1, y - 1));
-        }
-        if x < 7
-            && y > 0
-            && board.pieces[(y - 1) as usize][(x + 1) as usize].color != color
-            && board.pieces[(y - 1) as usize][(x + 1) as usize].piece_type != PieceType::EMPTY
-        {
-            valid_moves.push(Move(x + 1, y - 1));
-        }
-    }
+// One move to every square in the set that is not taken by a piece of the same side
+fn target_moves(square: Square, board: &Board, color: Color, targets: Bitboard) -> Vec<Move> {
+    return squares(targets & !board.occupancy(color))
+        .map(|to| step_move(board, square, to))
+        .collect();
+}
 
-    return valid_moves;
+pub fn bishop_legal_moves(square: Square, board: Board, color: Color) -> Vec<Move> {
+    let attacks = bishop_attacks(square, board.occupied());
+    return target_moves(square, &board, color, attacks);
 }
 
-pub fn rook_legal_moves(x: i32, y: i32, board: Board, color: Color) -> Vec<Move> {
+pub fn pawn_legal_moves(square: Square, board: &Board, color: Color) -> Vec<Move> {
     let mut valid_moves: Vec<Move> = Vec::new();
-
-    // 1. check straight line, y axis
-
-    // 1.1 check up dir
-    for i in (y + 1)..8 {
-        // check if anything here, if not add move and continue loop, otherwise break out
-
-        if board.pieces[i as usize][x as usize].color == Color::EMPTY {
-            valid_moves.push(Move(x, i));
-        } else if board.pieces[i as usize][x as usize].color != color {
-            valid_moves.push(Move(x, i));
-            break;
-        } else {
-            break;
-        }
-    }
-    // 1.2 check down dir
-    for i in (0..y).rev() {
-        // check if anything here, if not add move and continue loop, otherwise break out
-        if board.pieces[i as usize][x as usize].color == Color::EMPTY {
-            valid_moves.push(Move(x, i));
-        } else if board.pieces[i as usize][x as usize].color != color {
-            valid_moves.push(Move(x, i));
-            break;
-        } else {
-            break;
-        }
-    }
-
-    // 2. check straight line in x-axis
-
-    //2.1 check to right dir
-    for i in (x + 1)..8 {
-        if board.pieces[y as usize][i as usize].color == Color::EMPTY {
-            valid_moves.push(Move(i, y));
-        } else if board.pieces[y as usize][i as usize].color != color {
-            valid_moves.push(Move(i, y));
-            break;
-        } else {
-            break;
+    // White moves up the board, black down
+    let (dir, start_row) = match color {
+        Color::WHITE => (-1, 6),
+        Color::BLACK => (1, 1),
+        Color::EMPTY => return valid_moves,
+    };
+    let occupied = board.occupied();
+
+    if let Some(one_step) = square.offset(0, dir) {
+        if !contains(occupied, one_step) {
+            push_pawn_move(
+                &mut valid_moves,
+                Move::new(square, one_step, MoveKind::QUIET),
+            );
+            match one_step.offset(0, dir) {
+                Some(two_steps) if square.y() == start_row && !contains(occupied, two_steps) => {
+                    valid_moves.push(Move::new(square, two_steps, MoveKind::DOUBLEPUSH));
+                }
+                _ => {}
+            }
         }
     }
-
-    // 2.2 check left dir
-    for i in (0..x).rev() {
-        if board.pieces[y as usize][i as usize].color == Color::EMPTY {
-            valid_moves.push(Move(i, y));
-        } else if board.pieces[y as usize][i as usize].color != color {
-            valid_moves.push(Move(i, y));
-            break;
-        } else {
-            break;
-        }
+    let captures = pawn_attacks(square, color) & board.occupancy(color.opposite());
+    for target in squares(captures) {
+        push_pawn_move(
+            &mut valid_moves,
+            Move::new(square, target, MoveKind::CAPTURE),
+        );
     }
 
     return valid_moves;
 }
 
-pub fn possible_moves_for_color(game: &mut Game, color: Color) -> Vec<Move> {
-    let opposing_pieces: Vec<(&Piece, usize)> = game
-        .board
-        .pieces
-        .iter()
-        .flatten()
-        .enumerate()
-        .map(|(i, piece)| (piece, i))
-        .filter(|(piece, _index)| piece.color == color)
-        .collect();
+pub fn rook_legal_moves(square: Square, board: Board, color: Color) -> Vec<Move> {
+    let attacks = rook_attacks(square, board.occupied());
+    return target_moves(square, &board, color, attacks);
+}
 
-    let possible_moves: Vec<Move> = opposing_pieces
-        .iter()
-        .map(|(_piece, index)| {
-            let (row, col) = index_to_col_row(*index).unwrap();
-
-            let pseudo_moves = get_pseudo_legal_moves(game.board, col, row, color);
-            let legal_moves = filter_illegal_moves(&(game.board), pseudo_moves, color, col, row);
-            if legal_moves.len() > 0 {
-                println!("{:?}", legal_moves);
-                println!("{:?} {:?}", col, row);
-            }
-            return legal_moves;
-        })
-        .flatten()
+pub fn possible_moves_for_color(game: &Game, color: Color) -> Vec<Move> {
+    let legality = Legality::new(&game.board, color);
+    return squares(game.board.occupancy(color))
+        .flat_map(|square| legal_game_moves(game, square, &legality))
         .collect();
-    return possible_moves;
 }
 
-pub fn knight_legal_moves(x: i32, y: i32, board: Board, color: Color) -> Vec<Move> {
-    let mut valid_moves: Vec<Move> = Vec::new();
-
-    let a = vec![-2, 2];
-    let b = vec![-1, 1];
-
+pub fn knight_legal_moves(square: Square, board: Board, color: Color) -> Vec<Move> {
     // The knight can either move 2 steps horizontally or 2 steps vertically
-    // Case 1: two steps horizontally
-    // Valid transforms: (-2, 1) (-2, -1), (2, -1), (2, 1)
-    // Basically, cartesian product: {2, -2} x {1, -1}
-    // Case 2: two steps vertically
-    // Valid moves (-1, -2), (-1, 2) and so on
-    // Cartesian product: {1, -1} x {2, -2}
-    // Valid moves: {1, -1} x {2, -2} + {-2, 2} x {-1, 1}
-    let two_steps_vertical = cartesian_product(&a, &b);
-    let two_steps_horizontal = cartesian_product(&b, &a);
-    let possible_moves = [two_steps_horizontal, two_steps_vertical].concat();
-    for possible_move in possible_moves {
-        if possible_move.0 + x >= 0
-            && (possible_move.0 + x) <= 7
-            && (possible_move.1 + y) >= 0
-            && (possible_move.1 + y) <= 7
-            && board.pieces[(y + possible_move.1) as usize][(x + possible_move.0) as usize].color
-                != color
-        {
-            valid_moves.push(Move(x + possible_move.0, y + possible_move.1));
-        }
-    }
-
-    return valid_moves;
+    return target_moves(square, &board, color, knight_attacks(square));
 }
 
 // TODO: Fix naming, little confusing with valid and possible moves
-pub fn king_legal_moves(x: i32, y: i32, board: Board, color: Color) -> Vec<Move> {
-    let mut valid_moves: Vec<Move> = Vec::new();
-
-    // Pseudo legal moves
-    // up, down, right, left
-    if (y + 1) <= 7 && board.pieces[(y + 1) as usize][x as usize].color != color {
-        valid_moves.push(Move(x, y + 1));
-    }
-    if (y - 1) >= 0 && board.pieces[(y - 1) as usize][x as usize].color != color {
-        valid_moves.push(Move(x, y - 1));
-    }
-    if (x + 1) <= 7 && board.pieces[y as usize][(x + 1) as usize].color != color {
-        valid_moves.push(Move(x + 1, y));
-    }
-    if (x - 1) >= 0 && board.pieces[y as usize][(x - 1) as usize].color != color {
-        valid_moves.push(Move(x - 1, y));
-    }
-
-    // moves can be represented as {-1, 1} x {-1, 1}
-    let a = vec![-1, 1];
-    for possible_move in cartesian_product(&a, &a) {
-        if possible_move.0 + x >= 0
-            && (possible_move.0 + x) <= 7
-            && (possible_move.1 + y) >= 0
-            && (possible_move.1 + y) <= 7
-            && board.pieces[(y + possible_move.1) as usize][(x + possible_move.0) as usize].color
-                != color
-        {
-            valid_moves.push(Move(x + possible_move.0, y + possible_move.1));
-        }
-    }
-    return valid_moves;
+pub fn king_legal_moves(square: Square, board: Board, color: Color) -> Vec<Move> {
+    // Pseudo legal moves, one step in every direction
+    return target_moves(square, &board, color, king_attacks(square));
 }
 
-pub fn queen_legal_moves(x: i32, y: i32, board: Board, color: Color) -> Vec<Move> {
+pub fn queen_legal_moves(square: Square, board: Board, color: Color) -> Vec<Move> {
     // https://www.chess.com/terms/chess-queen
     // The valid moves for the queen is basically the union of the valid moves for the bishop and rook
-    let diagonal_moves = bishop_legal_moves(x, y, board, color);
-    let horizontal_vertical_moves = rook_legal_moves(x, y, board, color);
-    return [diagonal_moves, horizontal_vertical_moves].concat();
+    let attacks = queen_attacks(square, board.occupied());
+    return target_moves(square, &board, color, attacks);
 }
@@ -0,0 +1,138 @@
+use std::{fmt, str::FromStr};
+
+use crate::error::ChessError;
+
+// A position on the board that is always valid.
+// Stored as the index into the board seen as a flat list, a8 is 0, h8 is 7 and h1 is 63,
+// the same order as Board::pieces.
+// x is the file with 0 as the a file and y is the row from the top, so pieces[y][x]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct Square(u8);
+
+impl Square {
+    pub fn new(x: i32, y: i32) -> Option<Square> {
+        if !(0..8).contains(&x) || !(0..8).contains(&y) {
+            return None;
+        }
+        return Some(Square((y * 8 + x) as u8));
+    }
+
+    pub fn from_index(index: usize) -> Option<Square> {
+        if index >= 64 {
+            return None;
+        }
+        return Some(Square(index as u8));
+    }
+
+    // file is 0 for the a file, rank is 1 to 8 like in "e4"
+    pub fn from_file_rank(file: i32, rank: i32) -> Option<Square> {
+        return Square::new(file, 8 - rank);
+    }
+
+    // Every square, in the order of Board::pieces
+    pub fn all() -> impl Iterator<Item = Square> {
+        return (0..64).map(Square);
+    }
+
+    pub fn index(self) -> usize {
+        return self.0 as usize;
+    }
+
+    pub fn x(self) -> i32 {
+        return (self.0 % 8) as i32;
+    }
+
+    pub fn y(self) -> i32 {
+        return (self.0 / 8) as i32;
+    }
+
+    pub fn file(self) -> i32 {
+        return self.x();
+    }
+
+    pub fn rank(self) -> i32 {
+        return 8 - self.y();
+    }
+
+    // The square dx files to the right and dy rows down, None if that is outside the board
+    pub fn offset(self, dx: i32, dy: i32) -> Option<Square> {
+        return Square::new(self.x() + dx, self.y() + dy);
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let file = (b'a' + self.x() as u8) as char;
+        return write!(f, "{}{}", file, self.rank());
+    }
+}
+
+impl FromStr for Square {
+    type Err = ChessError;
+
+    // Reads a square like "e4"
+    fn from_str(name: &str) -> Result<Square, ChessError> {
+        let mut chars = name.chars();
+        let (Some(file), Some(rank), None) = (chars.next(), chars.next(), chars.next()) else {
+            return Err(ChessError::INVALIDSQUARE);
+        };
+        if !('a'..='h').contains(&file) {
+            return Err(ChessError::INVALIDSQUARE);
+        }
+        let rank = rank.to_digit(10).ok_or(ChessError::INVALIDSQUARE)? as i32;
+        return Square::from_file_rank(file as i32 - 'a' as i32, rank)
+            .ok_or(ChessError::INVALIDSQUARE);
+    }
+}
+
+impl TryFrom<(i32, i32)> for Square {
+    type Error = ChessError;
+
+    // From (x, y) with y as the row from the top
+    fn try_from(position: (i32, i32)) -> Result<Square, ChessError> {
+        return Square::new(position.0, position.1).ok_or(ChessError::OUTOFBOUNDS(position));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Square;
+    use crate::error::ChessError;
+
+    #[test]
+    fn square_conversions() {
+        let e4: Square = "e4".parse().unwrap();
+        assert_eq!((e4.x(), e4.y()), (4, 4));
+        assert_eq!((e4.file(), e4.rank()), (4, 4));
+        assert_eq!(e4.index(), 36);
+        assert_eq!(e4.to_string(), "e4");
+        assert_eq!(Square::new(4, 4), Some(e4));
+        assert_eq!(Square::from_index(36), Some(e4));
+        assert_eq!(Square::from_file_rank(4, 4), Some(e4));
+        assert_eq!(Square::try_from((4, 4)), Ok(e4));
+
+        assert_eq!(Square::from_index(0).unwrap().to_string(), "a8");
+        assert_eq!(Square::from_index(63).unwrap().to_string(), "h1");
+        assert_eq!(Square::all().count(), 64);
+    }
+
+    #[test]
+    fn invalid_squares() {
+        assert_eq!(Square::new(8, 0), None);
+        assert_eq!(Square::new(0, -1), None);
+        assert_eq!(Square::from_index(64), None);
+        assert_eq!(Square::from_file_rank(0, 0), None);
+        assert_eq!(
+            Square::try_from((-1, 3)),
+            Err(ChessError::OUTOFBOUNDS((-1, 3)))
+        );
+        for name in ["", "e", "e9", "i4", "e44", "E4"] {
+            assert_eq!(name.parse::<Square>(), Err(ChessError::INVALIDSQUARE));
+        }
+
+        let h1 = Square::from_index(63).unwrap();
+        assert_eq!(h1.offset(1, 0), None);
+        assert_eq!(h1.offset(0, 1), None);
+        assert_eq!(h1.offset(-1, -1).unwrap().to_string(), "g2");
+    }
+}
@@ -0,0 +1,59 @@
+use super::square::Square;
+
+// Zobrist hashing: every piece on every square, the side to move, each castling right and each
+// en passant file has its own random number. The key of a position is all its numbers xored
+// together, so a move only has to xor out what changed and xor in the new values
+
+const PIECE_KEYS: usize = 2 * 6 * 64;
+const BLACK_TO_MOVE: usize = PIECE_KEYS;
+const CASTLING: usize = BLACK_TO_MOVE + 1;
+const EN_PASSANT: usize = CASTLING + 4;
+
+const KEYS: [u64; EN_PASSANT + 8] = random_keys();
+
+// splitmix64, the numbers are built when the crate is compiled and are the same every run
+const fn random_keys() -> [u64; EN_PASSANT + 8] {
+    let mut keys = [0; EN_PASSANT + 8];
+    let mut state: u64 = 0x0123_4567_89AB_CDEF;
+    let mut i = 0;
+    while i < keys.len() {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    return keys;
+}
+
+// color and piece_type are the indices the board uses for its bitboards
+pub fn piece_key(color: usize, piece_type: usize, square: Square) -> u64 {
+    return KEYS[(color * 6 + piece_type) * 64 + square.index()];
+}
+
+pub fn black_to_move_key() -> u64 {
+    return KEYS[BLACK_TO_MOVE];
+}
+
+// The castling rights in the order of the fen string: K, Q, k, q
+pub fn castling_key(right: usize) -> u64 {
+    return KEYS[CASTLING + right];
+}
+
+pub fn en_passant_key(file: i32) -> u64 {
+    return KEYS[EN_PASSANT + file as usize];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn keys_are_different() {
+        let keys: HashSet<u64> = KEYS.iter().copied().collect();
+        assert_eq!(keys.len(), KEYS.len());
+        assert!(!keys.contains(&0));
+    }
+}
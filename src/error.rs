@@ -0,0 +1,145 @@
+use std::{error::Error, fmt};
+
+use crate::board::{parser::FenError, pieces::Color, square::Square};
+use crate::notation::SanError;
+
+// Why a move is not legal
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IllegalMoveReason {
+    // The piece does not move that way
+    UNREACHABLE,
+    // A piece of the same side stands on the position
+    OCCUPIED,
+    // There are pieces in the way
+    PATHBLOCKED,
+    // The piece is pinned to the king, moving it would put the king in check
+    PINNED,
+    // The king would move to an attacked position
+    KINGINTOCHECK,
+    // The king is in check, or would be after the move, and the move does not prevent it
+    KINGINCHECK,
+    // The pawn beside did not just move two positions
+    ENPASSANTUNAVAILABLE,
+    // A promotion piece was given for a move that does not promote, or the piece is a pawn or king
+    NOPROMOTION,
+}
+
+impl fmt::Display for IllegalMoveReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IllegalMoveReason::UNREACHABLE => write!(f, "the piece can not move there"),
+            IllegalMoveReason::OCCUPIED => write!(f, "your own piece stands there"),
+            IllegalMoveReason::PATHBLOCKED => write!(f, "the path is blocked"),
+            IllegalMoveReason::PINNED => write!(f, "the piece is pinned to the king"),
+            IllegalMoveReason::KINGINTOCHECK => write!(f, "the king would move into check"),
+            IllegalMoveReason::KINGINCHECK => write!(f, "the king would be in check"),
+            IllegalMoveReason::ENPASSANTUNAVAILABLE => {
+                write!(f, "en passant is no longer available")
+            }
+            IllegalMoveReason::NOPROMOTION => write!(f, "the move can not promote to that piece"),
+        }
+    }
+}
+
+// Why a castling move is not allowed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CastlingReason {
+    // The king or the rook has moved, or the castling right was not given in the fen string
+    NORIGHTS,
+    INCHECK,
+    // There are pieces between the king and the rook
+    PATHBLOCKED,
+    // The king would pass through or end up on an attacked position
+    ATTACKEDSQUARE,
+}
+
+impl fmt::Display for CastlingReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CastlingReason::NORIGHTS => write!(f, "the right to castle is lost"),
+            CastlingReason::INCHECK => write!(f, "the king is in check"),
+            CastlingReason::PATHBLOCKED => {
+                write!(f, "there are pieces between the king and the rook")
+            }
+            CastlingReason::ATTACKEDSQUARE => {
+                write!(f, "the king would pass through an attacked position")
+            }
+        }
+    }
+}
+
+// Every error the crate can return
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChessError {
+    // The position is not on the board
+    OUTOFBOUNDS((i32, i32)),
+    // There is no piece on the square
+    NOPIECE(Square),
+    // The piece belongs to the side that is not to move, holds the side whose turn it is
+    NOTYOURTURN(Color),
+    ILLEGALMOVE(IllegalMoveReason),
+    // A pawn moves to the last rank but no promotion piece was chosen
+    PROMOTIONPENDING,
+    CASTLINGNOTALLOWED(CastlingReason),
+    GAMEOVER,
+    INVALIDFEN(FenError),
+    // A move in algebraic notation could not be read or does not match exactly one legal move
+    INVALIDSAN(SanError),
+    // A move in UCI notation like "e2e4" or "e7e8q" could not be read
+    INVALIDUCI,
+    // A square name like "e4" could not be read
+    INVALIDSQUARE,
+    // Color::EMPTY was passed where a side was needed
+    NOSIDE,
+    // GameResult::ONGOING was passed where the game needs to end
+    NORESULT,
+    // Neither threefold repetition nor the 50 move rule allows a draw to be claimed
+    NODRAWTOCLAIM,
+}
+
+impl fmt::Display for ChessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChessError::OUTOFBOUNDS((x, y)) => {
+                write!(f, "x: {}, y: {} is not on the board", x, y)
+            }
+            ChessError::NOPIECE(square) => write!(f, "No piece on {}", square),
+            ChessError::NOTYOURTURN(turn) => write!(f, "Not your turn, {} is to move", turn),
+            ChessError::ILLEGALMOVE(reason) => write!(f, "Illegal move, {}", reason),
+            ChessError::PROMOTIONPENDING => write!(f, "Choose a piece to promote the pawn to"),
+            ChessError::CASTLINGNOTALLOWED(reason) => {
+                write!(f, "Castling is not allowed, {}", reason)
+            }
+            ChessError::GAMEOVER => write!(f, "Game is already over"),
+            ChessError::INVALIDFEN(error) => write!(f, "{}", error),
+            ChessError::INVALIDSAN(error) => write!(f, "{}", error),
+            ChessError::INVALIDUCI => write!(f, "Not a move in UCI notation"),
+            ChessError::INVALIDSQUARE => write!(f, "Not a square on the board"),
+            ChessError::NOSIDE => write!(f, "No side was given"),
+            ChessError::NORESULT => write!(f, "An ongoing game has no result"),
+            ChessError::NODRAWTOCLAIM => write!(f, "No draw to claim"),
+        }
+    }
+}
+
+impl Error for ChessError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ChessError::INVALIDFEN(error) => Some(error),
+            ChessError::INVALIDSAN(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<FenError> for ChessError {
+    fn from(error: FenError) -> ChessError {
+        return ChessError::INVALIDFEN(error);
+    }
+}
+
+impl From<SanError> for ChessError {
+    fn from(error: SanError) -> ChessError {
+        return ChessError::INVALIDSAN(error);
+    }
+}
@@ -1,101 +1,527 @@
+use std::fmt;
+
 use crate::board::{
-    board::Board,
-    pieces::{Color, Move, PieceType},
+    bitboard::Bitboard,
+    board::{insufficient_material, insufficient_material_for, Board},
+    parser::{parse_fen, parse_fen_with_defaults, serialize_fen, Fen},
+    pieces::{
+        en_passant_moves, illegal_move_reason, legal_moves_from, move_piece, play_legal_move,
+        possible_moves_for_color, redo_piece_move, undo_piece_move, update_check_state, Color,
+        Move, Piece, PieceType,
+    },
+    square::Square,
+    zobrist::{black_to_move_key, castling_key, en_passant_key},
 };
+use crate::error::ChessError;
+use crate::notation::{move_to_san, parse_san, parse_uci};
+use crate::pgn::write_pgn;
 
+#[derive(Clone)]
 pub struct Game {
     pub board: Board,
     pub turn: Color,
-    // (right, left)
+    // Castling rights, lost when the king or the rook moves.
+    // (right, left) as seen by the player from their side of the board, so .0 is the
+    // king side for white and the queen side for black. The rest of the crate uses the same order
     pub can_castle_white: (bool, bool),
     pub can_castle_black: (bool, bool),
     pub white_in_check: bool,
     pub black_in_check: bool,
     pub white_captures: Vec<PieceType>,
     pub black_captures: Vec<PieceType>,
-    pub white_pawn_promotion: Option<(i32, i32)>,
-    pub black_pawn_promotion: Option<(i32, i32)>,
     pub white_moves: Vec<(Move, PieceType)>,
     pub black_moves: Vec<(Move, PieceType)>,
-    pub white_en_passant: Option<(i32, i32)>,
-    pub black_en_passant: Option<(i32, i32)>,
+    // The square a pawn skipped over with a double push in the last move
+    pub en_passant: Option<Square>,
     pub check_mate_white: bool,
     pub check_mate_black: bool,
-    pub white_repetitions: i32,
-    pub black_repetitions: i32,
+    // If the side to move is not in check but has no legal moves
+    pub stalemate: bool,
+    // Every position of the game so far, the current position last
+    // as their Zobrist keys
+    pub positions: Vec<u64>,
+    // Number of half moves since the last pawn move or capture
+    pub halfmove_clock: u32,
+    // Starts at 1 and goes up after every black move
+    pub fullmove_number: u32,
+    // Set when the game is ended by something else than the board, like a resignation or a claimed draw
+    pub ended: Option<(GameResult, Termination)>,
+    // The moves played so far, oldest first, with what is needed to take them back
+    pub history: Vec<MoveRecord>,
+    // Moves that have been taken back and can be played again with redo
+    pub undone: Vec<MoveRecord>,
+}
+
+// Everything a move overwrites, so the move can be taken back without keeping a copy of the board
+#[derive(Clone, Copy, Debug)]
+pub struct MoveRecord {
+    pub piece_move: Move,
+    // The piece before it moved
+    pub moved_piece: Piece,
+    // The piece that was captured, empty if the move was not a capture
+    pub captured_piece: Piece,
+    pub can_castle_white: (bool, bool),
+    pub can_castle_black: (bool, bool),
+    pub en_passant: Option<Square>,
+    pub white_in_check: bool,
+    pub black_in_check: bool,
+    pub check_mate_white: bool,
+    pub check_mate_black: bool,
+    pub stalemate: bool,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+}
+
+// The result of a game, written like in PGN files
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameResult {
+    WHITEWINS,
+    BLACKWINS,
+    DRAW,
+    ONGOING,
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameResult::WHITEWINS => write!(f, "1-0"),
+            GameResult::BLACKWINS => write!(f, "0-1"),
+            GameResult::DRAW => write!(f, "1/2-1/2"),
+            GameResult::ONGOING => write!(f, "*"),
+        }
+    }
+}
+
+// Why a game ended
+// REPETITION covers both a claimed threefold and a fivefold repetition,
+// FIFTYMOVES both a claimed 50 move draw and the automatic one after 75 moves
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Termination {
+    CHECKMATE,
+    STALEMATE,
+    RESIGNATION,
+    TIMEOUT,
+    REPETITION,
+    FIFTYMOVES,
+    INSUFFICIENTMATERIAL,
+    AGREEMENT,
+    ADJUDICATION,
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Termination::CHECKMATE => write!(f, "Checkmate"),
+            Termination::STALEMATE => write!(f, "Stalemate"),
+            Termination::RESIGNATION => write!(f, "Resignation"),
+            Termination::TIMEOUT => write!(f, "Timeout"),
+            Termination::REPETITION => write!(f, "Repetition"),
+            Termination::FIFTYMOVES => write!(f, "Fifty move rule"),
+            Termination::INSUFFICIENTMATERIAL => write!(f, "Insufficient material"),
+            Termination::AGREEMENT => write!(f, "Agreement"),
+            Termination::ADJUDICATION => write!(f, "Adjudication"),
+        }
+    }
 }
 
 impl Game {
+    // A new game from the starting position, or from a fen string.
+    // Fields missing at the end of the fen string get the values of a new game, see parse_fen_with_defaults.
+    //
+    // # Panics
+    //
+    // On a fen string that from_fen would reject even with the missing fields filled in, like a
+    // board without a black king or with both kings in check. Use from_fen to get the error instead
     pub fn new(fen: Option<String>) -> Game {
-        Game {
-            board: Board::init_board(fen),
+        if let Some(fen) = fen {
+            let fen = parse_fen_with_defaults(&fen).expect("Invalid fen string");
+            return Game::from_parsed_fen(fen);
+        }
+        let board = Board::init_board(None);
+        return Game::from_parsed_fen(Fen {
+            board,
             turn: Color::WHITE,
-            can_castle_white: (false, false),
-            can_castle_black: (false, false),
+            can_castle_white: (true, true),
+            can_castle_black: (true, true),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        });
+    }
+
+    // Loads a position from all six fields of a fen string: piece placement, side to move,
+    // castling rights, en passant position, halfmove clock and fullmove number
+    pub fn from_fen(fen: &str) -> Result<Game, ChessError> {
+        return Ok(Game::from_parsed_fen(parse_fen(fen)?));
+    }
+
+    fn from_parsed_fen(fen: Fen) -> Game {
+        let mut game = Game {
+            board: fen.board,
+            turn: fen.turn,
+            can_castle_white: fen.can_castle_white,
+            can_castle_black: fen.can_castle_black,
             white_in_check: false,
             black_in_check: false,
             white_captures: Vec::new(),
             black_captures: Vec::new(),
             white_moves: Vec::new(),
             black_moves: Vec::new(),
-            white_pawn_promotion: None,
-            black_pawn_promotion: None,
-            white_en_passant: None,
-            black_en_passant: None,
+            en_passant: fen.en_passant,
             check_mate_white: false,
             check_mate_black: false,
-            white_repetitions: 0,
-            black_repetitions: 0,
-        }
+            stalemate: false,
+            positions: Vec::new(),
+            halfmove_clock: fen.halfmove_clock,
+            fullmove_number: fen.fullmove_number,
+            ended: None,
+            history: Vec::new(),
+            undone: Vec::new(),
+        };
+        // The position can already be check, mate or stalemate
+        update_check_state(&mut game);
+        let position = game.zobrist_key();
+        game.positions.push(position);
+        return game;
     }
 
-    pub fn game_over(&self) -> Option<Color> {
+    // The result of the game, ONGOING while it is still being played
+    pub fn result(&self) -> GameResult {
+        if let Some((result, _termination)) = self.ended {
+            return result;
+        }
         if self.check_mate_white {
-            return Some(Color::BLACK);
+            return GameResult::BLACKWINS;
         }
         if self.check_mate_black {
-            return Some(Color::WHITE);
+            return GameResult::WHITEWINS;
+        }
+        if self.termination().is_some() {
+            return GameResult::DRAW;
+        }
+        return GameResult::ONGOING;
+    }
+
+    // Why the game ended, None if it is still being played
+    pub fn termination(&self) -> Option<Termination> {
+        if let Some((_result, termination)) = self.ended {
+            return Some(termination);
+        }
+        if self.check_mate_white || self.check_mate_black {
+            return Some(Termination::CHECKMATE);
+        }
+        if self.stalemate {
+            return Some(Termination::STALEMATE);
         }
-        if self.white_repetitions >= 3 {
-            return Some(Color::EMPTY);
+        // A threefold repetition has to be claimed, fivefold ends the game directly
+        if self.repetitions() >= 5 {
+            return Some(Termination::REPETITION);
         }
-        if self.black_repetitions >= 3 {
-            return Some(Color::EMPTY);
+        // Same for 50 moves and 75 moves without pawn moves or captures
+        if self.halfmove_clock >= 150 {
+            return Some(Termination::FIFTYMOVES);
+        }
+        if insufficient_material(&self.board) {
+            return Some(Termination::INSUFFICIENTMATERIAL);
         }
         return None;
     }
+
+    pub fn game_over(&self) -> bool {
+        return self.result() != GameResult::ONGOING;
+    }
+
+    // Ends a game that is still being played, the ways to end a game that are not rules of the board
+    fn end(&mut self, result: GameResult, termination: Termination) -> Result<(), ChessError> {
+        if self.game_over() {
+            return Err(ChessError::GAMEOVER);
+        }
+        self.ended = Some((result, termination));
+        return Ok(());
+    }
+
+    pub fn resign(&mut self, color: Color) -> Result<(), ChessError> {
+        let result = match color {
+            Color::WHITE => GameResult::BLACKWINS,
+            Color::BLACK => GameResult::WHITEWINS,
+            Color::EMPTY => return Err(ChessError::NOSIDE),
+        };
+        return self.end(result, Termination::RESIGNATION);
+    }
+
+    // The time of color ran out, a draw if the other side can not checkmate
+    pub fn timeout(&mut self, color: Color) -> Result<(), ChessError> {
+        let (winner, result) = match color {
+            Color::WHITE => (Color::BLACK, GameResult::BLACKWINS),
+            Color::BLACK => (Color::WHITE, GameResult::WHITEWINS),
+            Color::EMPTY => return Err(ChessError::NOSIDE),
+        };
+        if insufficient_material_for(&self.board, winner) {
+            return self.end(GameResult::DRAW, Termination::TIMEOUT);
+        }
+        return self.end(result, Termination::TIMEOUT);
+    }
+
+    pub fn agree_draw(&mut self) -> Result<(), ChessError> {
+        return self.end(GameResult::DRAW, Termination::AGREEMENT);
+    }
+
+    // Ends the game with a result decided outside of the board, for example by an arbiter
+    pub fn adjudicate(&mut self, result: GameResult) -> Result<(), ChessError> {
+        if result == GameResult::ONGOING {
+            return Err(ChessError::NORESULT);
+        }
+        return self.end(result, Termination::ADJUDICATION);
+    }
+
+    // Claims a draw by threefold repetition or the 50 move rule
+    pub fn claim_draw(&mut self) -> Result<(), ChessError> {
+        if self.is_threefold_repetition() {
+            return self.end(GameResult::DRAW, Termination::REPETITION);
+        }
+        if self.is_fifty_moves() {
+            return self.end(GameResult::DRAW, Termination::FIFTYMOVES);
+        }
+        return Err(ChessError::NODRAWTOCLAIM);
+    }
+
+    // If 50 moves by each side have been made without a pawn move or capture,
+    // which allows a player to claim a draw
+    pub fn is_fifty_moves(&self) -> bool {
+        return self.halfmove_clock >= 100;
+    }
+
+    // The current position as a fen string with all six fields, can be loaded with from_fen
+    pub fn to_fen(&self) -> String {
+        return serialize_fen(&Fen {
+            board: self.board,
+            turn: self.turn,
+            can_castle_white: self.can_castle_white,
+            can_castle_black: self.can_castle_black,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+        });
+    }
+
+    // The en passant position if a pawn of the side to move can legally capture there.
+    // A double push that can not be captured does not make the position different
+    fn capturable_en_passant(&self) -> Option<Square> {
+        return self.en_passant.filter(|target| {
+            // The pawns that can capture stand beside the pawn that moved
+            let row = if target.y() == 2 { 3 } else { 4 };
+            [target.x() - 1, target.x() + 1]
+                .into_iter()
+                .filter_map(|x| Square::new(x, row))
+                .any(|side| !en_passant_moves(self, side).is_empty())
+        });
+    }
+
+    // 64 bit Zobrist key of the position: the pieces, the side to move, the castling rights and
+    // the en passant file when a pawn can legally capture there.
+    // The key of the pieces is updated with every move and taken back move, the rest is added here
+    pub fn zobrist_key(&self) -> u64 {
+        let mut key = self.board.zobrist_key();
+        if self.turn == Color::BLACK {
+            key ^= black_to_move_key();
+        }
+        // In the order of the fen string, KQkq
+        let rights = [
+            self.can_castle_white.0,
+            self.can_castle_white.1,
+            self.can_castle_black.1,
+            self.can_castle_black.0,
+        ];
+        for (right, has_right) in rights.into_iter().enumerate() {
+            if has_right {
+                key ^= castling_key(right);
+            }
+        }
+        if let Some(target) = self.capturable_en_passant() {
+            key ^= en_passant_key(target.x());
+        }
+        return key;
+    }
+
+    // How many times the current position has occurred in the game, the current one included.
+    // Positions are compared by their Zobrist keys
+    pub fn repetitions(&self) -> usize {
+        let Some(current) = self.positions.last() else {
+            return 0;
+        };
+        return self
+            .positions
+            .iter()
+            .filter(|position| *position == current)
+            .count();
+    }
+
+    // If the current position has occurred three times, which allows a player to claim a draw
+    pub fn is_threefold_repetition(&self) -> bool {
+        return self.repetitions() >= 3;
+    }
+
+    // All the legal moves for the piece on the square, including en passant
+    pub fn legal_moves_from(&self, square: Square) -> Vec<Move> {
+        return legal_moves_from(self, square);
+    }
+
+    // All the legal moves for the side whose turn it is
+    pub fn legal_moves(&self) -> Vec<Move> {
+        return possible_moves_for_color(self, self.turn);
+    }
+
+    // The pieces that give check to the king of the side to move
+    pub fn checkers(&self) -> Bitboard {
+        return self.board.checkers(self.turn);
+    }
+
+    // The pieces of the side to move that are pinned to their king
+    pub fn pinned(&self) -> Bitboard {
+        return self.board.pinned(self.turn);
+    }
+
+    // Why the piece on from can not move to to, None if it can
+    // Promotions are not looked at, a pawn that can reach the last rank has a legal move
+    pub fn illegal_move_reason(&self, from: Square, to: Square) -> Option<ChessError> {
+        return illegal_move_reason(self, from, to);
+    }
+
+    // Plays a move, preferably one picked from legal_moves
+    pub fn make_move(&mut self, piece_move: Move) -> Result<(), ChessError> {
+        return move_piece(piece_move, self);
+    }
+
+    // The move in standard algebraic notation, like Nf3 or exd5, in the current position
+    pub fn san(&self, piece_move: Move) -> Result<String, ChessError> {
+        return move_to_san(self, piece_move);
+    }
+
+    // The legal move written in standard algebraic notation, like Nf3, exd6, O-O-O or e8=Q+
+    pub fn parse_san(&self, san: &str) -> Result<Move, ChessError> {
+        return parse_san(self, san);
+    }
+
+    // The legal move in the long algebraic notation of the UCI protocol, like e2e4 or e7e8q.
+    // Display of a move writes this notation
+    pub fn parse_uci(&self, uci: &str) -> Result<Move, ChessError> {
+        return parse_uci(self, uci);
+    }
+
+    // Plays the moves in UCI notation separated by whitespace, like "e2e4 e7e5 g1f3".
+    // Stops at the first move that can not be played, the moves before it stay played
+    pub fn play_uci(&mut self, moves: &str) -> Result<(), ChessError> {
+        for uci in moves.split_whitespace() {
+            let legal_move = self.parse_uci(uci)?;
+            play_legal_move(self, legal_move);
+        }
+        return Ok(());
+    }
+
+    // The game in PGN with the given tags as pairs of name and value, see pgn::write_pgn
+    pub fn to_pgn(&self, tags: &[(&str, &str)]) -> String {
+        return write_pgn(self, tags);
+    }
+
+    // Takes back the last move, returns the move or None if no moves have been made
+    pub fn undo(&mut self) -> Option<Move> {
+        return undo_piece_move(self);
+    }
+
+    // Plays the last taken back move again, returns the move or None if there is nothing to redo
+    pub fn redo(&mut self) -> Option<Move> {
+        return redo_piece_move(self);
+    }
+
+    // Counts the positions reached after depth half moves from the current position,
+    // compare with known numbers to find bugs in the move generator.
+    // The moves are taken back afterwards so the game is left as it was
+    pub fn perft(&mut self, depth: u32) -> u64 {
+        return self.keep_state(|game| perft_nodes(game, depth));
+    }
+
+    // perft split by the first move, to find the move where a wrong count comes from
+    pub fn divide(&mut self, depth: u32) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return vec![];
+        }
+        return self.keep_state(|game| {
+            let mut counts = Vec::new();
+            for piece_move in game.legal_moves() {
+                play_legal_move(game, piece_move);
+                counts.push((piece_move, perft_nodes(game, depth - 1)));
+                undo_piece_move(game);
+            }
+            return counts;
+        });
+    }
+
+    // Undoing moves fills the redo stack and clears how the game ended, put them back afterwards
+    fn keep_state<T>(&mut self, run: impl FnOnce(&mut Game) -> T) -> T {
+        let undone = std::mem::take(&mut self.undone);
+        let ended = self.ended;
+        let result = run(self);
+        self.undone = undone;
+        self.ended = ended;
+        return result;
+    }
+}
+
+// Moves are played even if the game is over by the rules, only the legal moves count
+fn perft_nodes(game: &mut Game, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = game.legal_moves();
+    // The moves of the last half move only have to be counted
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut nodes = 0;
+    for piece_move in moves {
+        play_legal_move(game, piece_move);
+        nodes += perft_nodes(game, depth - 1);
+        undo_piece_move(game);
+    }
+    return nodes;
 }
 
 #[cfg(test)]
 mod tests {
     use crate::board::{
         self,
-        pieces::{get_legal_moves, make_castle_move, promote_pawn},
+        pieces::{get_legal_moves, MoveKind},
     };
 
     use super::*;
+    use crate::error::{CastlingReason, IllegalMoveReason};
+    use std::collections::HashSet;
+
+    fn sq(name: &str) -> Square {
+        return name.parse().unwrap();
+    }
 
     #[test]
     fn reject_invalid_pawn_move() {
         let mut game = Game::new(None);
 
-        let invalid_move = Move(7, 7);
+        let invalid_move = Move::new(sq("b7"), sq("h1"), MoveKind::QUIET);
 
-        let result = board::pieces::move_piece(invalid_move, 1, 1, &mut game);
-        assert!(result.is_err());
+        let result = board::pieces::move_piece(invalid_move, &mut game);
+        assert_eq!(result, Err(ChessError::NOTYOURTURN(Color::WHITE)));
     }
 
     #[test]
     fn pawn_move_updates_game_state() {
         let mut game = Game::new(None);
 
-        // Move white pawn from e2 to e3
-        let pawn_move = Move(4, 4);
-        let result = board::pieces::move_piece(pawn_move, 4, 6, &mut game);
+        // Move white pawn from e2 to e4
+        let pawn_move = Move::new(sq("e2"), sq("e4"), MoveKind::DOUBLEPUSH);
+        let result = game.make_move(pawn_move);
 
         assert!(result.is_ok());
 
-        println!("{:?}", game.board.pieces[6][4].piece_type);
+        println!("{:?}", game.board[sq("e2")].piece_type);
 
         // Check that the game state has been updated
         assert_eq!(game.turn, Color::BLACK);
@@ -106,10 +532,30 @@ mod tests {
         assert!(game.black_captures.is_empty());
 
         // Verify board state
-        assert_eq!(game.board.pieces[4][4].piece_type, PieceType::PAWN);
-        assert_eq!(game.board.pieces[4][4].color, Color::WHITE);
-        assert_eq!(game.board.pieces[5][4].piece_type, PieceType::EMPTY);
-        assert_eq!(game.board.pieces[5][4].color, Color::EMPTY);
+        assert_eq!(game.board[sq("e4")].piece_type, PieceType::PAWN);
+        assert_eq!(game.board[sq("e4")].color, Color::WHITE);
+        assert_eq!(game.board[sq("e2")].piece_type, PieceType::EMPTY);
+        assert_eq!(game.board[sq("e2")].color, Color::EMPTY);
+    }
+
+    #[test]
+    fn legal_moves_for_start_position() {
+        let game = Game::new(None);
+
+        let legal_moves = game.legal_moves();
+
+        // 16 pawn moves and 4 knight moves
+        assert_eq!(legal_moves.len(), 20);
+        assert_eq!(
+            legal_moves
+                .iter()
+                .filter(|piece_move| piece_move.kind == MoveKind::DOUBLEPUSH)
+                .count(),
+            8
+        );
+        assert!(legal_moves
+            .iter()
+            .all(|piece_move| piece_move.kind != MoveKind::CAPTURE));
     }
 
     #[test]
@@ -118,28 +564,174 @@ mod tests {
             "r3kbnr/1PQ1pppp/1pnp4/p3P3/2B5/7N/P1PP1PPP/RNB1K2R",
         )));
 
-        let promotion_move = Move(1, 0);
-        let result = board::pieces::move_piece(promotion_move, 1, 1, &mut game);
+        let promotion_moves: Vec<Move> = game
+            .legal_moves_from(sq("b7"))
+            .into_iter()
+            .filter(|piece_move| piece_move.to == sq("b8"))
+            .collect();
+        // One move for every piece the pawn can be promoted to
+        assert_eq!(promotion_moves.len(), 4);
+
+        let promotion_move =
+            Move::new(sq("b7"), sq("b8"), MoveKind::QUIET).with_promotion(PieceType::QUEEN);
+        let result = game.make_move(promotion_move);
 
         assert!(result.is_ok());
-        assert_eq!(game.white_pawn_promotion, Some((1, 0)));
-        assert_eq!(game.board.pieces[0][1].piece_type, PieceType::PAWN);
+        assert_eq!(game.board[sq("b8")].piece_type, PieceType::QUEEN);
+        assert_eq!(game.board[sq("b8")].color, Color::WHITE);
+    }
+
+    #[test]
+    fn move_errors() {
+        let mut game = Game::new(None);
+        assert_eq!(
+            game.make_move(Move::new(sq("e4"), sq("e5"), MoveKind::QUIET)),
+            Err(ChessError::NOPIECE(sq("e4")))
+        );
+        assert_eq!(
+            game.make_move(Move::new(sq("e7"), sq("e5"), MoveKind::DOUBLEPUSH)),
+            Err(ChessError::NOTYOURTURN(Color::WHITE))
+        );
+
+        // The bishop on d2 can not leave the king on e1 in check from the queen on b4
+        let mut game = Game::from_fen("4k3/8/8/8/1q6/8/3B4/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            game.make_move(Move::new(sq("d2"), sq("e3"), MoveKind::QUIET)),
+            Err(ChessError::ILLEGALMOVE(IllegalMoveReason::PINNED))
+        );
+        assert_eq!(
+            ChessError::ILLEGALMOVE(IllegalMoveReason::PINNED).to_string(),
+            "Illegal move, the piece is pinned to the king"
+        );
     }
 
     #[test]
-    fn make_white_pawn_promotion() {
+    fn explain_illegal_moves() {
+        let reason = |fen: &str, from: &str, to: &str| {
+            let game = Game::from_fen(fen).unwrap();
+            return game.illegal_move_reason(sq(from), sq(to));
+        };
+        let illegal = |reason: IllegalMoveReason| Some(ChessError::ILLEGALMOVE(reason));
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+        assert_eq!(reason(start, "e2", "e4"), None);
+        assert_eq!(
+            reason(start, "e7", "e5"),
+            Some(ChessError::NOTYOURTURN(Color::WHITE))
+        );
+        assert_eq!(
+            reason(start, "b1", "d2"),
+            illegal(IllegalMoveReason::OCCUPIED)
+        );
+        assert_eq!(
+            reason(start, "c1", "f4"),
+            illegal(IllegalMoveReason::PATHBLOCKED)
+        );
+        assert_eq!(
+            reason(start, "b1", "b3"),
+            illegal(IllegalMoveReason::UNREACHABLE)
+        );
+        // The pawn on e4 is blocked by the pawn on e5
+        assert_eq!(
+            reason("4k3/8/8/4p3/4P3/8/8/4K3 w - - 0 1", "e4", "e5"),
+            illegal(IllegalMoveReason::PATHBLOCKED)
+        );
+        // The rook on e2 is pinned by the rook on e8, but can still move along the pin
+        let pinned = "4r1k1/8/8/8/8/8/4R3/4K3 w - - 0 1";
+        assert_eq!(
+            reason(pinned, "e2", "a2"),
+            illegal(IllegalMoveReason::PINNED)
+        );
+        assert_eq!(reason(pinned, "e2", "e8"), None);
+        // The king can not step onto the e file
+        assert_eq!(
+            reason("4r1k1/8/8/8/8/8/8/3K4 w - - 0 1", "d1", "e1"),
+            illegal(IllegalMoveReason::KINGINTOCHECK)
+        );
+        // In check from the rook on e8, the knight move does not help
+        assert_eq!(
+            reason("4r1k1/8/8/8/8/8/8/1N2K3 w - - 0 1", "b1", "c3"),
+            illegal(IllegalMoveReason::KINGINCHECK)
+        );
+        // Black pushed d7 to d5 two moves ago
+        assert_eq!(
+            reason("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1", "e5", "d6"),
+            illegal(IllegalMoveReason::ENPASSANTUNAVAILABLE)
+        );
+        assert_eq!(
+            reason("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5", "d6"),
+            None
+        );
+        // The bishop on c4 attacks f1, b1 does not matter for castling to the queen side
+        let castling = "r3k2r/8/8/8/2b5/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(
+            reason(castling, "e1", "g1"),
+            Some(ChessError::CASTLINGNOTALLOWED(
+                CastlingReason::ATTACKEDSQUARE
+            ))
+        );
+        assert_eq!(
+            reason("r3k2r/8/8/8/8/8/8/Rb2K2R w KQkq - 0 1", "e1", "c1"),
+            Some(ChessError::CASTLINGNOTALLOWED(CastlingReason::PATHBLOCKED))
+        );
+        assert_eq!(
+            reason("r3k2r/8/8/8/4b3/8/8/R3K2R w KQkq - 0 1", "e1", "c1"),
+            None
+        );
+        // Two files along the rank away from the starting square is not castling
+        assert_eq!(
+            reason("4k3/p7/8/8/8/8/4K3/8 w - - 0 1", "e2", "g2"),
+            illegal(IllegalMoveReason::UNREACHABLE)
+        );
+        assert_eq!(
+            reason("4k3/p7/8/8/8/8/8/3K4 w - - 0 1", "d1", "f1"),
+            illegal(IllegalMoveReason::UNREACHABLE)
+        );
+    }
+
+    #[test]
+    fn castling_errors() {
+        let castle = |fen: &str, to: &str| {
+            let mut game = Game::from_fen(fen).unwrap();
+            return game.make_move(Move::new(sq("e1"), sq(to), MoveKind::CASTLE));
+        };
+        assert_eq!(
+            castle("4k3/8/8/8/8/8/8/R3K2R w K - 0 1", "c1"),
+            Err(ChessError::CASTLINGNOTALLOWED(CastlingReason::NORIGHTS))
+        );
+        assert_eq!(
+            castle("4k3/4r3/8/8/8/8/8/R3K2R w KQ - 0 1", "g1"),
+            Err(ChessError::CASTLINGNOTALLOWED(CastlingReason::INCHECK))
+        );
+        assert_eq!(
+            castle("4k3/8/8/8/8/8/8/R3KB1R w KQ - 0 1", "g1"),
+            Err(ChessError::CASTLINGNOTALLOWED(CastlingReason::PATHBLOCKED))
+        );
+        assert_eq!(
+            castle("4k3/5r2/8/8/8/8/8/R3K2R w KQ - 0 1", "g1"),
+            Err(ChessError::CASTLINGNOTALLOWED(
+                CastlingReason::ATTACKEDSQUARE
+            ))
+        );
+
+        let error = Game::from_fen("4k3/8/8/8/8/8/8/4K4 w - - 0 1")
+            .err()
+            .unwrap();
+        assert!(matches!(error, ChessError::INVALIDFEN(_)));
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn reject_pawn_move_to_last_rank_without_promotion() {
         let mut game = Game::new(Some(String::from(
             "r3kbnr/1PQ1pppp/1pnp4/p3P3/2B5/7N/P1PP1PPP/RNB1K2R",
         )));
 
-        let promotion_move = Move(1, 0);
-        board::pieces::move_piece(promotion_move, 1, 1, &mut game).ok();
+        let result = game.make_move(Move::new(sq("b7"), sq("b8"), MoveKind::QUIET));
 
-        assert!(promote_pawn(&mut game, PieceType::QUEEN, Color::WHITE).is_ok());
-        assert_eq!(game.board.pieces[0][1].piece_type, PieceType::QUEEN);
-        assert_eq!(game.white_pawn_promotion, None);
-        // test that black pawn promotion fails
-        assert!(promote_pawn(&mut game, PieceType::QUEEN, Color::BLACK).is_err())
+        assert_eq!(result, Err(ChessError::PROMOTIONPENDING));
+        assert_eq!(game.board[sq("b7")].piece_type, PieceType::PAWN);
+        assert_eq!(game.turn, Color::WHITE);
     }
 
     #[test]
@@ -149,14 +741,22 @@ mod tests {
         )));
 
         // make space for castling
-        board::pieces::move_piece(Move(4, 6), 5, 7, &mut game).ok();
+        game.make_move(Move::new(sq("f1"), sq("e2"), MoveKind::QUIET))
+            .ok();
+        game.make_move(Move::new(sq("a5"), sq("a4"), MoveKind::QUIET))
+            .ok();
 
-        let result = make_castle_move(&mut game, Color::WHITE, 1);
+        let castle_move = Move::new(sq("e1"), sq("g1"), MoveKind::CASTLE);
+        assert!(game.legal_moves().contains(&castle_move));
+
+        let result = game.make_move(castle_move);
         assert!(result.is_ok());
-        assert_eq!(game.can_castle_white, (true, false));
+        assert_eq!(game.can_castle_white, (false, false));
 
-        assert_eq!(game.board.pieces[7][6].piece_type, PieceType::KING);
-        assert_eq!(game.board.pieces[7][5].piece_type, PieceType::ROOK);
+        assert_eq!(game.board[sq("g1")].piece_type, PieceType::KING);
+        assert_eq!(game.board[sq("f1")].piece_type, PieceType::ROOK);
+        assert_eq!(game.board[sq("h1")].piece_type, PieceType::EMPTY);
+        assert_eq!(game.turn, Color::BLACK);
     }
 
     #[test]
@@ -166,19 +766,24 @@ mod tests {
         )));
         game.turn = Color::BLACK;
 
-        let en_passant_move = Move(3, 3);
-        let result = board::pieces::move_piece(en_passant_move, 3, 1, &mut game);
+        let en_passant_move = Move::new(sq("d7"), sq("d5"), MoveKind::DOUBLEPUSH);
+        let result = game.make_move(en_passant_move);
 
-        let white_pawn_legal_moves = get_legal_moves(game.board, 3, 3, Color::WHITE);
-        println!("{:?}", white_pawn_legal_moves);
+        let black_pawn_legal_moves = get_legal_moves(game.board, sq("d5"), Color::WHITE);
+        println!("{:?}", black_pawn_legal_moves);
 
         println!("{:?}", game.black_moves);
         assert!(result.is_ok());
-        assert_eq!(game.board.pieces[3][3].piece_type, PieceType::PAWN);
-        assert_eq!(game.board.pieces[3][3].color, Color::BLACK);
-        assert_eq!(game.white_en_passant, Some((3, 3)));
-        assert_eq!(game.board.pieces[3][3].has_moved, true);
-        assert_eq!(white_pawn_legal_moves.len(), 1);
+        assert_eq!(game.board[sq("d5")].piece_type, PieceType::PAWN);
+        assert_eq!(game.board[sq("d5")].color, Color::BLACK);
+        assert_eq!(game.en_passant, Some(sq("d6")));
+        assert!(game.board[sq("d5")].has_moved);
+        assert_eq!(black_pawn_legal_moves.len(), 1);
+        assert!(game.legal_moves_from(sq("e5")).contains(&Move::new(
+            sq("e5"),
+            sq("d6"),
+            MoveKind::ENPASSANT
+        )));
     }
 
     #[test]
@@ -188,21 +793,355 @@ mod tests {
         )));
         game.turn = Color::BLACK;
 
-        let en_passant_move = Move(3, 3);
-        let black_move_result = board::pieces::move_piece(en_passant_move, 3, 1, &mut game);
+        let en_passant_move = Move::new(sq("d7"), sq("d5"), MoveKind::DOUBLEPUSH);
+        let black_move_result = game.make_move(en_passant_move);
 
         assert!(black_move_result.is_ok());
-        assert_eq!(game.white_en_passant, Some((3, 3)));
+        assert_eq!(game.en_passant, Some(sq("d6")));
+
+        let result = game.make_move(Move::new(sq("e5"), sq("d6"), MoveKind::ENPASSANT));
+
+        assert!(result.is_ok());
+        assert_eq!(game.board[sq("d5")].piece_type, PieceType::EMPTY);
+        assert_eq!(game.board[sq("d5")].color, Color::EMPTY);
+        assert_eq!(game.en_passant, None);
+        assert_eq!(game.black_captures.len(), 1);
+        assert!(game.black_captures.contains(&PieceType::PAWN));
+        assert_eq!(game.board[sq("d6")].piece_type, PieceType::PAWN);
+        assert_eq!(game.board[sq("d6")].color, Color::WHITE);
+    }
+
+    #[test]
+    fn test_black_make_en_passant() {
+        let mut game = Game::new(Some(String::from(
+            "rnbqkbnr/pppp1ppp/8/8/4p3/8/PPPPPPPP/RNBQKBNR",
+        )));
+
+        assert!(game
+            .make_move(Move::new(sq("d2"), sq("d4"), MoveKind::DOUBLEPUSH))
+            .is_ok());
+        assert_eq!(game.en_passant, Some(sq("d3")));
 
-        board::pieces::en_passant_move(&mut game, Color::WHITE, 4, 3);
+        let result = game.make_move(Move::new(sq("e4"), sq("d3"), MoveKind::ENPASSANT));
 
-        assert_eq!(game.board.pieces[3][3].piece_type, PieceType::EMPTY);
-        assert_eq!(game.board.pieces[3][3].color, Color::EMPTY);
-        assert_eq!(game.white_en_passant, None);
-        assert_eq!(game.white_captures.len(), 1);
-        assert!(game.white_captures.contains(&PieceType::PAWN));
-        assert_eq!(game.board.pieces[2][3].piece_type, PieceType::PAWN);
-        assert_eq!(game.board.pieces[2][3].color, Color::WHITE);
+        assert!(result.is_ok());
+        assert_eq!(game.board[sq("d4")].piece_type, PieceType::EMPTY);
+        assert_eq!(game.board[sq("d3")].piece_type, PieceType::PAWN);
+        assert_eq!(game.board[sq("d3")].color, Color::BLACK);
+        assert_eq!(game.white_captures, vec![PieceType::PAWN]);
+        assert_eq!(game.turn, Color::WHITE);
+    }
+
+    #[test]
+    fn en_passant_only_right_after_double_push() {
+        let mut game = Game::new(Some(String::from(
+            "rnbqkbnr/pppppppp/8/4P3/8/8/PPPP1PPP/RNBQKBNR",
+        )));
+        game.turn = Color::BLACK;
+
+        game.make_move(Move::new(sq("d7"), sq("d5"), MoveKind::DOUBLEPUSH))
+            .ok();
+        game.make_move(Move::new(sq("g1"), sq("f3"), MoveKind::QUIET))
+            .ok();
+        game.make_move(Move::new(sq("g8"), sq("f6"), MoveKind::QUIET))
+            .ok();
+
+        assert_eq!(game.en_passant, None);
+        let result = game.make_move(Move::new(sq("e5"), sq("d6"), MoveKind::ENPASSANT));
+        assert_eq!(
+            result,
+            Err(ChessError::ILLEGALMOVE(
+                IllegalMoveReason::ENPASSANTUNAVAILABLE
+            ))
+        );
+        assert_eq!(game.board[sq("d5")].piece_type, PieceType::PAWN);
+    }
+
+    #[test]
+    fn moving_rook_loses_castling_right() {
+        let mut game = Game::new(Some(String::from("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R")));
+        assert_eq!(game.can_castle_white, (true, true));
+        assert_eq!(game.can_castle_black, (true, true));
+
+        // Move the rooks away and back again
+        game.make_move(Move::new(sq("h1"), sq("g1"), MoveKind::QUIET))
+            .ok();
+        game.make_move(Move::new(sq("a8"), sq("b8"), MoveKind::QUIET))
+            .ok();
+        game.make_move(Move::new(sq("g1"), sq("h1"), MoveKind::QUIET))
+            .ok();
+        game.make_move(Move::new(sq("b8"), sq("a8"), MoveKind::QUIET))
+            .ok();
+
+        assert_eq!(game.can_castle_white, (false, true));
+        assert_eq!(game.can_castle_black, (false, true));
+        let castle_moves: Vec<Move> = game
+            .legal_moves()
+            .into_iter()
+            .filter(|piece_move| piece_move.kind == MoveKind::CASTLE)
+            .collect();
+        assert_eq!(
+            castle_moves,
+            vec![Move::new(sq("e1"), sq("c1"), MoveKind::CASTLE)]
+        );
+    }
+
+    #[test]
+    fn black_capture_with_promotion() {
+        let mut game = Game::new(Some(String::from(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPp/RNBQKBN1",
+        )));
+        game.turn = Color::BLACK;
+
+        let promotion_move =
+            Move::new(sq("h2"), sq("g1"), MoveKind::CAPTURE).with_promotion(PieceType::KNIGHT);
+        let result = game.make_move(promotion_move);
+
+        assert!(result.is_ok());
+        assert_eq!(game.board[sq("g1")].piece_type, PieceType::KNIGHT);
+        assert_eq!(game.board[sq("g1")].color, Color::BLACK);
+        assert_eq!(game.board[sq("h2")].piece_type, PieceType::EMPTY);
+        assert_eq!(game.white_captures, vec![PieceType::KNIGHT]);
+        assert_eq!(game.turn, Color::WHITE);
+    }
+
+    #[test]
+    fn undo_restores_game_state() {
+        let mut game = Game::new(None);
+        let start = game.board;
+
+        game.make_move(Move::new(sq("e2"), sq("e4"), MoveKind::DOUBLEPUSH))
+            .ok();
+        game.make_move(Move::new(sq("d7"), sq("d5"), MoveKind::DOUBLEPUSH))
+            .ok();
+        game.make_move(Move::new(sq("e4"), sq("d5"), MoveKind::CAPTURE))
+            .ok();
+        assert_eq!(game.black_captures, vec![PieceType::PAWN]);
+
+        assert_eq!(
+            game.undo(),
+            Some(Move::new(sq("e4"), sq("d5"), MoveKind::CAPTURE))
+        );
+        assert_eq!(game.en_passant, Some(sq("d6")));
+        assert!(game.black_captures.is_empty());
+        assert_eq!(game.board[sq("d5")].color, Color::BLACK);
+        assert_eq!(game.turn, Color::WHITE);
+
+        game.undo();
+        game.undo();
+        assert_eq!(game.undo(), None);
+
+        assert!(game.board.pieces() == start.pieces());
+        assert_eq!(game.turn, Color::WHITE);
+        assert_eq!(game.en_passant, None);
+        assert!(game.white_moves.is_empty());
+        assert!(game.black_moves.is_empty());
+        assert!(game.history.is_empty());
+    }
+
+    #[test]
+    fn undo_special_moves() {
+        let mut game = Game::new(Some(String::from(
+            "r3k2r/1P1ppppp/8/4P3/8/8/PPPP1PPP/R3K2R",
+        )));
+        let start = game.board;
+
+        // castling, en passant and a promotion with capture
+        game.make_move(Move::new(sq("e1"), sq("g1"), MoveKind::CASTLE))
+            .ok();
+        game.make_move(Move::new(sq("d7"), sq("d5"), MoveKind::DOUBLEPUSH))
+            .ok();
+        game.make_move(Move::new(sq("e5"), sq("d6"), MoveKind::ENPASSANT))
+            .ok();
+        game.make_move(Move::new(sq("e8"), sq("d8"), MoveKind::QUIET))
+            .ok();
+        game.make_move(
+            Move::new(sq("b7"), sq("a8"), MoveKind::CAPTURE).with_promotion(PieceType::QUEEN),
+        )
+        .ok();
+        assert_eq!(game.history.len(), 5);
+        assert_eq!(game.board[sq("a8")].piece_type, PieceType::QUEEN);
+
+        while game.undo().is_some() {}
+
+        assert!(game.board.pieces() == start.pieces());
+        assert_eq!(game.can_castle_white, (true, true));
+        assert_eq!(game.can_castle_black, (true, true));
+        assert!(game.white_captures.is_empty());
+        assert!(game.black_captures.is_empty());
+        assert_eq!(game.turn, Color::WHITE);
+    }
+
+    #[test]
+    fn redo_replays_undone_moves() {
+        let mut game = Game::new(None);
+        let first_move = Move::new(sq("b1"), sq("c3"), MoveKind::QUIET);
+        let second_move = Move::new(sq("b8"), sq("c6"), MoveKind::QUIET);
+        game.make_move(first_move).ok();
+        game.make_move(second_move).ok();
+        let after_moves = game.board;
+
+        game.undo();
+        game.undo();
+        assert_eq!(game.redo(), Some(first_move));
+        assert_eq!(game.redo(), Some(second_move));
+        assert_eq!(game.redo(), None);
+        assert!(game.board.pieces() == after_moves.pieces());
+        assert_eq!(game.turn, Color::WHITE);
+
+        // A new move after undo clears the moves that could be redone
+        game.undo();
+        assert!(game
+            .make_move(Move::new(sq("a7"), sq("a6"), MoveKind::QUIET))
+            .is_ok());
+        assert_eq!(game.redo(), None);
+    }
+
+    #[test]
+    fn checkers_and_pinned_for_side_to_move() {
+        let mut game = Game::new(None);
+        for (from, to, kind) in [
+            ("e2", "e4", MoveKind::DOUBLEPUSH),
+            ("d7", "d5", MoveKind::DOUBLEPUSH),
+            ("f1", "b5", MoveKind::QUIET),
+        ] {
+            game.make_move(Move::new(sq(from), sq(to), kind)).unwrap();
+        }
+        assert_eq!(game.checkers(), 1 << sq("b5").index());
+        assert_eq!(game.pinned(), 0);
+
+        game.make_move(Move::new(sq("c7"), sq("c6"), MoveKind::QUIET))
+            .unwrap();
+        assert_eq!(game.checkers(), 0);
+        game.make_move(Move::new(sq("b5"), sq("a4"), MoveKind::QUIET))
+            .unwrap();
+        game.make_move(Move::new(sq("d8"), sq("a5"), MoveKind::QUIET))
+            .unwrap();
+        // The queen on a5 pins the d2 pawn
+        assert_eq!(game.pinned(), 1 << sq("d2").index());
+        assert!(game.legal_moves_from(sq("d2")).is_empty());
+    }
+
+    // Node counts from https://www.chessprogramming.org/Perft_Results
+    #[test]
+    fn perft_reference_positions() {
+        let positions: [(&str, &[u64]); 6] = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                &[20, 400, 8902, 197281],
+            ),
+            // Kiwipete
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                &[48, 2039, 97862],
+            ),
+            (
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                &[14, 191, 2812, 43238],
+            ),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                &[6, 264, 9467],
+            ),
+            (
+                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                &[44, 1486, 62379],
+            ),
+            (
+                "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+                &[46, 2079, 89890],
+            ),
+        ];
+        for (fen, counts) in positions {
+            let mut game = Game::from_fen(fen).unwrap();
+            for (depth, nodes) in counts.iter().enumerate() {
+                assert_eq!(
+                    game.perft(depth as u32 + 1),
+                    *nodes,
+                    "{} depth {}",
+                    fen,
+                    depth + 1
+                );
+            }
+            // Nothing is left behind
+            assert_eq!(game.to_fen(), fen);
+            assert!(game.history.is_empty());
+        }
+    }
+
+    #[test]
+    fn divide_adds_up_to_perft() {
+        let mut game = Game::new(None);
+        game.make_move(Move::new(sq("e2"), sq("e4"), MoveKind::DOUBLEPUSH))
+            .unwrap();
+        game.undo();
+
+        let counts = game.divide(3);
+        assert_eq!(counts.len(), 20);
+        assert_eq!(counts.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+        let e4 = counts
+            .iter()
+            .find(|(piece_move, _)| piece_move.to == sq("e4"))
+            .unwrap();
+        assert_eq!(e4.1, 600);
+        // The undone move can still be redone
+        assert!(game.redo().is_some());
+        assert_eq!(game.perft(0), 1);
+        assert!(game.divide(0).is_empty());
+    }
+
+    #[test]
+    fn zobrist_keys() {
+        let play = |moves: &[(&str, &str)]| {
+            let mut game = Game::new(None);
+            for (from, to) in moves {
+                game.make_move(Move::new(sq(from), sq(to), MoveKind::QUIET))
+                    .unwrap();
+            }
+            return game;
+        };
+        // The same position through different move orders
+        let mut first = play(&[("g1", "f3"), ("g8", "f6"), ("b1", "c3")]);
+        let second = play(&[("b1", "c3"), ("g8", "f6"), ("g1", "f3")]);
+        assert_eq!(first.zobrist_key(), second.zobrist_key());
+        assert!(first.board == second.board);
+        let boards: HashSet<Board> = [first.board, second.board].into_iter().collect();
+        assert_eq!(boards.len(), 1);
+        let loaded = Game::from_fen(&first.to_fen()).unwrap();
+        assert_eq!(loaded.zobrist_key(), first.zobrist_key());
+
+        // Taking back a move gives the key from before the move
+        let start = Game::new(None);
+        first.undo();
+        first.undo();
+        first.undo();
+        assert_eq!(first.zobrist_key(), start.zobrist_key());
+
+        let black_to_move =
+            Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert_ne!(black_to_move.zobrist_key(), start.zobrist_key());
+        assert_eq!(black_to_move.board.zobrist_key(), start.board.zobrist_key());
+        let no_queen_side =
+            Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w Kkq - 0 1").unwrap();
+        assert_ne!(no_queen_side.zobrist_key(), start.zobrist_key());
+
+        // The en passant file only counts when a pawn can capture
+        let mut game = Game::new(None);
+        game.make_move(Move::new(sq("e2"), sq("e4"), MoveKind::DOUBLEPUSH))
+            .unwrap();
+        let without_target = Game::from_fen(&game.to_fen().replace(" e3 ", " - ")).unwrap();
+        assert_eq!(game.zobrist_key(), without_target.zobrist_key());
+        let fen = "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3";
+        let with_target = Game::from_fen(fen).unwrap();
+        let without_target = Game::from_fen(&fen.replace(" e3 ", " - ")).unwrap();
+        assert_ne!(with_target.zobrist_key(), without_target.zobrist_key());
+
+        // bxc6 would leave the king in check from the rook on h5, so c6 does not count either
+        let fen = "8/8/8/KPp4r/8/8/8/7k w - c6 0 1";
+        let pinned = Game::from_fen(fen).unwrap();
+        let without_target = Game::from_fen(&fen.replace(" c6 ", " - ")).unwrap();
+        assert_eq!(pinned.zobrist_key(), without_target.zobrist_key());
+        assert_eq!(pinned.positions, without_target.positions);
     }
 
     #[test]
@@ -213,13 +1152,376 @@ mod tests {
         game.turn = Color::BLACK;
 
         // move black queen to mate position
-        let queen_move = Move(7, 4);
-        let result = board::pieces::move_piece(queen_move, 3, 0, &mut game);
+        let queen_move = Move::new(sq("d8"), sq("h4"), MoveKind::QUIET);
+        let result = game.make_move(queen_move);
+
+        assert!(result.is_ok());
+        assert!(game.check_mate_white);
+        assert!(!game.check_mate_black);
+        assert_eq!(game.result(), GameResult::BLACKWINS);
+        assert_eq!(game.termination(), Some(Termination::CHECKMATE));
+    }
+
+    #[test]
+    fn test_black_stalemate() {
+        let mut game = Game::new(Some(String::from("k7/8/8/2Q5/8/8/8/7K")));
+
+        let queen_move = Move::new(sq("c5"), sq("b6"), MoveKind::QUIET);
+        let result = game.make_move(queen_move);
+
+        assert!(result.is_ok());
+        assert!(game.stalemate);
+        assert!(!game.check_mate_black);
+        assert!(game.legal_moves().is_empty());
+        assert_eq!(game.termination(), Some(Termination::STALEMATE));
+        assert_eq!(game.result(), GameResult::DRAW);
+
+        game.undo();
+        assert!(!game.stalemate);
+        assert_eq!(game.result(), GameResult::ONGOING);
+    }
+
+    #[test]
+    fn no_stalemate_when_pieces_can_move() {
+        let mut game = Game::new(Some(String::from("k7/p7/8/2Q5/8/8/8/7K")));
+
+        // The king is stuck but the pawn can still move
+        let result = game.make_move(Move::new(sq("c5"), sq("b6"), MoveKind::QUIET));
+
+        assert!(result.is_ok());
+        assert!(!game.stalemate);
+        assert_eq!(game.result(), GameResult::ONGOING);
+    }
+
+    // Moves the knights out and back again, which repeats the position after every four moves
+    fn shuffle_knights(game: &mut Game) {
+        game.make_move(Move::new(sq("b1"), sq("c3"), MoveKind::QUIET))
+            .ok();
+        game.make_move(Move::new(sq("b8"), sq("c6"), MoveKind::QUIET))
+            .ok();
+        game.make_move(Move::new(sq("c3"), sq("b1"), MoveKind::QUIET))
+            .ok();
+        game.make_move(Move::new(sq("c6"), sq("b8"), MoveKind::QUIET))
+            .ok();
+    }
+
+    #[test]
+    fn threefold_and_fivefold_repetition() {
+        let mut game = Game::new(None);
+
+        shuffle_knights(&mut game);
+        assert_eq!(game.repetitions(), 2);
+        assert!(!game.is_threefold_repetition());
+
+        shuffle_knights(&mut game);
+        assert_eq!(game.repetitions(), 3);
+        assert!(game.is_threefold_repetition());
+        // Threefold repetition has to be claimed
+        assert_eq!(game.result(), GameResult::ONGOING);
+
+        shuffle_knights(&mut game);
+        shuffle_knights(&mut game);
+        assert_eq!(game.repetitions(), 5);
+        assert_eq!(game.termination(), Some(Termination::REPETITION));
+        assert_eq!(game.result(), GameResult::DRAW);
+
+        game.undo();
+        assert_eq!(game.repetitions(), 4);
+        assert_eq!(game.result(), GameResult::ONGOING);
+    }
+
+    #[test]
+    fn repetition_needs_same_castling_rights() {
+        let mut game = Game::new(Some(String::from("r3k2r/8/8/8/8/8/8/R3K2R")));
+
+        // Same pieces on the same positions three times, but the castling rights are lost after the first time
+        for _ in 0..2 {
+            game.make_move(Move::new(sq("e1"), sq("f1"), MoveKind::QUIET))
+                .ok();
+            game.make_move(Move::new(sq("e8"), sq("f8"), MoveKind::QUIET))
+                .ok();
+            game.make_move(Move::new(sq("f1"), sq("e1"), MoveKind::QUIET))
+                .ok();
+            game.make_move(Move::new(sq("f8"), sq("e8"), MoveKind::QUIET))
+                .ok();
+        }
+
+        assert_eq!(game.history.len(), 8);
+        assert_eq!(game.repetitions(), 2);
+        assert!(!game.is_threefold_repetition());
+    }
+
+    #[test]
+    fn checks_are_not_repetitions() {
+        let mut game = Game::new(Some(String::from("4k3/8/8/8/8/8/8/R3K3")));
+
+        // Three checks in a row in different positions
+        game.make_move(Move::new(sq("a1"), sq("a8"), MoveKind::QUIET))
+            .ok();
+        game.make_move(Move::new(sq("e8"), sq("d7"), MoveKind::QUIET))
+            .ok();
+        game.make_move(Move::new(sq("a8"), sq("a7"), MoveKind::QUIET))
+            .ok();
+        game.make_move(Move::new(sq("d7"), sq("c6"), MoveKind::QUIET))
+            .ok();
+        game.make_move(Move::new(sq("a7"), sq("a6"), MoveKind::QUIET))
+            .ok();
+
+        assert_eq!(game.history.len(), 5);
+        assert!(game.black_in_check);
+        assert_eq!(game.result(), GameResult::ONGOING);
+    }
+
+    #[test]
+    fn halfmove_clock_and_fullmove_number() {
+        let mut game = Game::new(None);
+
+        game.make_move(Move::new(sq("b1"), sq("c3"), MoveKind::QUIET))
+            .ok();
+        assert_eq!(game.halfmove_clock, 1);
+        assert_eq!(game.fullmove_number, 1);
+
+        game.make_move(Move::new(sq("b8"), sq("c6"), MoveKind::QUIET))
+            .ok();
+        assert_eq!(game.halfmove_clock, 2);
+        assert_eq!(game.fullmove_number, 2);
+
+        // Pawn move resets the clock
+        game.make_move(Move::new(sq("d2"), sq("d4"), MoveKind::DOUBLEPUSH))
+            .ok();
+        assert_eq!(game.halfmove_clock, 0);
+
+        game.make_move(Move::new(sq("c6"), sq("b4"), MoveKind::QUIET))
+            .ok();
+        game.make_move(Move::new(sq("a2"), sq("a3"), MoveKind::QUIET))
+            .ok();
+        assert_eq!(game.halfmove_clock, 0);
+        game.make_move(Move::new(sq("b4"), sq("d3"), MoveKind::QUIET))
+            .ok();
+        assert_eq!(game.halfmove_clock, 1);
+
+        // Capture resets the clock
+        game.make_move(Move::new(sq("c2"), sq("d3"), MoveKind::CAPTURE))
+            .ok();
+        assert_eq!(game.halfmove_clock, 0);
+        assert_eq!(game.fullmove_number, 4);
+
+        game.undo();
+        assert_eq!(game.halfmove_clock, 1);
+        assert_eq!(game.fullmove_number, 4);
+        game.undo();
+        assert_eq!(game.fullmove_number, 3);
+    }
 
+    #[test]
+    fn fifty_and_seventy_five_move_rule() {
+        let mut game = Game::new(Some(String::from("4k3/8/8/8/8/8/8/R3K3 w - - 98 70")));
+        assert_eq!(game.halfmove_clock, 98);
+        assert_eq!(game.fullmove_number, 70);
+
+        game.make_move(Move::new(sq("a1"), sq("a2"), MoveKind::QUIET))
+            .ok();
+        assert!(!game.is_fifty_moves());
+        game.make_move(Move::new(sq("e8"), sq("d8"), MoveKind::QUIET))
+            .ok();
+        assert!(game.is_fifty_moves());
+        // Fifty moves has to be claimed
+        assert_eq!(game.result(), GameResult::ONGOING);
+
+        let mut game = Game::new(Some(String::from("4k3/8/8/8/8/8/8/R3K3 w - - 149 90")));
+        game.make_move(Move::new(sq("a1"), sq("a2"), MoveKind::QUIET))
+            .ok();
+        assert_eq!(game.termination(), Some(Termination::FIFTYMOVES));
+        assert_eq!(game.result(), GameResult::DRAW);
+    }
+
+    #[test]
+    fn capturing_last_piece_ends_game() {
+        let mut game = Game::new(Some(String::from("8/8/4k3/8/4r3/4K3/8/8")));
+        assert_eq!(game.result(), GameResult::ONGOING);
+
+        // King takes the last rook
+        let result = game.make_move(Move::new(sq("e3"), sq("e4"), MoveKind::CAPTURE));
         assert!(result.is_ok());
-        assert_eq!(game.check_mate_white, true);
-        assert_eq!(game.check_mate_black, false);
-        assert_eq!(game.game_over(), Some(Color::BLACK));
+
+        assert_eq!(game.termination(), Some(Termination::INSUFFICIENTMATERIAL));
+        assert_eq!(game.result(), GameResult::DRAW);
+    }
+
+    #[test]
+    fn resign_and_agree_draw() {
+        let mut game = Game::new(None);
+        assert!(game.resign(Color::WHITE).is_ok());
+        assert_eq!(game.result(), GameResult::BLACKWINS);
+        assert_eq!(game.termination(), Some(Termination::RESIGNATION));
+        assert!(game.game_over());
+        // No moves or other endings after the game is over
+        assert_eq!(
+            game.make_move(Move::new(sq("b1"), sq("c3"), MoveKind::QUIET)),
+            Err(ChessError::GAMEOVER)
+        );
+        assert_eq!(game.agree_draw(), Err(ChessError::GAMEOVER));
+
+        let mut game = Game::new(None);
+        assert!(game.agree_draw().is_ok());
+        assert_eq!(game.result(), GameResult::DRAW);
+        assert_eq!(game.termination(), Some(Termination::AGREEMENT));
+
+        let mut game = Game::new(None);
+        assert_eq!(
+            game.adjudicate(GameResult::ONGOING),
+            Err(ChessError::NORESULT)
+        );
+        assert!(game.adjudicate(GameResult::WHITEWINS).is_ok());
+        assert_eq!(game.result(), GameResult::WHITEWINS);
+        assert_eq!(game.termination(), Some(Termination::ADJUDICATION));
+    }
+
+    #[test]
+    fn timeout_against_lone_king_is_draw() {
+        let mut game = Game::new(Some(String::from("8/8/4k3/8/8/4K3/8/7Q")));
+        assert!(game.timeout(Color::BLACK).is_ok());
+        assert_eq!(game.result(), GameResult::WHITEWINS);
+        assert_eq!(game.termination(), Some(Termination::TIMEOUT));
+
+        let mut game = Game::new(Some(String::from("8/8/4k3/8/8/4K3/8/7Q")));
+        assert!(game.timeout(Color::WHITE).is_ok());
+        assert_eq!(game.result(), GameResult::DRAW);
+        assert_eq!(game.termination(), Some(Termination::TIMEOUT));
+    }
+
+    #[test]
+    fn claim_draw_by_repetition() {
+        let mut game = Game::new(None);
+        assert_eq!(game.claim_draw(), Err(ChessError::NODRAWTOCLAIM));
+
+        shuffle_knights(&mut game);
+        shuffle_knights(&mut game);
+
+        assert!(game.claim_draw().is_ok());
+        assert_eq!(game.result(), GameResult::DRAW);
+        assert_eq!(game.termination(), Some(Termination::REPETITION));
+        assert_eq!(game.result().to_string(), "1/2-1/2");
+
+        // Taking back the last move takes back the claim as well
+        game.undo();
+        assert_eq!(game.result(), GameResult::ONGOING);
+    }
+
+    // Game::new only panics on a fen string that can not be played with any defaults
+    #[test]
+    fn new_game_from_a_short_fen() {
+        let game = Game::new(Some(String::from("4k3/8/8/8/8/8/8/4RK2")));
+        assert_eq!(game.turn, Color::BLACK);
+        assert_eq!(game.legal_moves().len(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid fen string")]
+    fn new_game_panics_on_an_invalid_fen() {
+        Game::new(Some(String::from("8/8/8/8/8/8/8/4K3")));
+    }
+
+    #[test]
+    fn from_fen_black_to_move() {
+        let game =
+            Game::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+
+        assert_eq!(game.turn, Color::BLACK);
+        assert_eq!(game.en_passant, Some(sq("e3")));
+        assert_eq!(game.fullmove_number, 1);
+        assert_eq!(game.legal_moves().len(), 20);
+        assert!(game
+            .legal_moves()
+            .iter()
+            .all(|piece_move| game.board[piece_move.from].color == Color::BLACK));
+    }
+
+    #[test]
+    fn from_fen_castling_rights() {
+        let game = Game::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w Kq - 0 1").unwrap();
+        let castle_moves: Vec<Move> = game
+            .legal_moves()
+            .into_iter()
+            .filter(|piece_move| piece_move.kind == MoveKind::CASTLE)
+            .collect();
+        assert_eq!(
+            castle_moves,
+            vec![Move::new(sq("e1"), sq("g1"), MoveKind::CASTLE)]
+        );
+
+        let game = Game::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b Kq - 0 1").unwrap();
+        let castle_moves: Vec<Move> = game
+            .legal_moves()
+            .into_iter()
+            .filter(|piece_move| piece_move.kind == MoveKind::CASTLE)
+            .collect();
+        assert_eq!(
+            castle_moves,
+            vec![Move::new(sq("e8"), sq("c8"), MoveKind::CASTLE)]
+        );
+    }
+
+    #[test]
+    fn from_fen_en_passant() {
+        let mut game =
+            Game::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3").unwrap();
+
+        let en_passant_move = Move::new(sq("d4"), sq("e3"), MoveKind::ENPASSANT);
+        assert!(game.legal_moves().contains(&en_passant_move));
+        assert!(game.make_move(en_passant_move).is_ok());
+        assert_eq!(game.board[sq("e4")].piece_type, PieceType::EMPTY);
+
+        // Same position without the en passant field
+        let game =
+            Game::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3").unwrap();
+        assert!(!game.legal_moves().contains(&en_passant_move));
+    }
+
+    #[test]
+    fn to_fen_after_moves() {
+        let mut game = Game::new(None);
+        assert_eq!(
+            game.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+
+        game.make_move(Move::new(sq("e2"), sq("e4"), MoveKind::DOUBLEPUSH))
+            .unwrap();
+        assert_eq!(
+            game.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+
+        game.make_move(Move::new(sq("g8"), sq("f6"), MoveKind::QUIET))
+            .unwrap();
+        game.make_move(Move::new(sq("e1"), sq("e2"), MoveKind::QUIET))
+            .unwrap();
+        let fen = game.to_fen();
+        assert_eq!(
+            fen,
+            "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPPKPPP/RNBQ1BNR b kq - 2 2"
+        );
+
+        // Loading the fen gives the same position back
+        let loaded = Game::from_fen(&fen).unwrap();
+        assert_eq!(loaded.to_fen(), fen);
+        assert_eq!(loaded.zobrist_key(), game.zobrist_key());
+        assert_eq!(loaded.legal_moves(), game.legal_moves());
+    }
+
+    #[test]
+    fn from_fen_already_over() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4R2K b - - 0 1").unwrap();
+        assert!(game.black_in_check);
+        assert_eq!(game.result(), GameResult::ONGOING);
+
+        let game = Game::from_fen("R3k3/8/4K3/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(game.check_mate_black);
+        assert_eq!(game.result(), GameResult::WHITEWINS);
+
+        let game = Game::from_fen("k7/8/1Q6/8/8/8/8/7K b - - 0 1").unwrap();
+        assert_eq!(game.termination(), Some(Termination::STALEMATE));
     }
 
     #[test]
@@ -228,13 +1530,14 @@ mod tests {
             "rnbqkbnr/ppppp2p/5p2/6p1/8/4P3/PPPP1PPP/RNBQKBNR",
         )));
 
-        let queen_move = Move(7, 3);
-        let result = board::pieces::move_piece(queen_move, 3, 7, &mut game);
+        let queen_move = Move::new(sq("d1"), sq("h5"), MoveKind::QUIET);
+        let result = game.make_move(queen_move);
 
         println!("{:?}", result.err());
         assert!(result.is_ok());
-        assert_eq!(game.check_mate_white, false);
-        assert_eq!(game.check_mate_black, true);
-        assert_eq!(game.game_over(), Some(Color::WHITE));
+        assert!(!game.check_mate_white);
+        assert!(game.check_mate_black);
+        assert_eq!(game.result(), GameResult::WHITEWINS);
+        assert_eq!(game.termination(), Some(Termination::CHECKMATE));
     }
 }
@@ -1,5 +1,11 @@
+// Explicit returns are the style used throughout the crate
+#![allow(clippy::needless_return)]
+
 pub mod board;
+pub mod error;
 pub mod game;
+pub mod notation;
+pub mod pgn;
 pub mod utils;
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
@@ -0,0 +1,559 @@
+use std::{error::Error, fmt};
+
+use crate::board::{
+    board::in_check,
+    pieces::{find_legal_move, play_legal_move, simulate_piece_move, Move, MoveKind, PieceType},
+    square::Square,
+};
+use crate::error::ChessError;
+use crate::game::Game;
+
+// Why a move in standard algebraic notation could not be turned into a move
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SanError {
+    // The text is not a move in algebraic notation
+    MALFORMED,
+    // More than one legal move matches, the piece has to be given with its file or rank
+    AMBIGUOUS,
+    // No legal move matches
+    ILLEGAL,
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SanError::MALFORMED => write!(f, "Not a move in algebraic notation"),
+            SanError::AMBIGUOUS => write!(f, "More than one legal move matches the notation"),
+            SanError::ILLEGAL => write!(f, "No legal move matches the notation"),
+        }
+    }
+}
+
+impl Error for SanError {}
+
+// The letter of the piece in standard algebraic notation, pawns have none
+pub fn piece_letter(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::KING => "K",
+        PieceType::QUEEN => "Q",
+        PieceType::ROOK => "R",
+        PieceType::BISHOP => "B",
+        PieceType::KNIGHT => "N",
+        PieceType::PAWN | PieceType::EMPTY => "",
+    }
+}
+
+// Standard algebraic notation of the move in the current position of the game,
+// like Nf3, exd5, Rad1, O-O, e8=Q+ or Qh5#. The move has to be legal
+pub fn move_to_san(game: &Game, piece_move: Move) -> Result<String, ChessError> {
+    let legal_move = find_legal_move(game, piece_move)?;
+    return Ok(legal_move_to_san(game, legal_move));
+}
+
+// The notation of a move from Game::legal_moves without checking it first, not even if the
+// game is over. Used by move_to_san and to write games that were played on after the end
+pub fn legal_move_to_san(game: &Game, legal_move: Move) -> String {
+    let piece = game.board[legal_move.from];
+    let mut san = String::new();
+
+    if legal_move.kind == MoveKind::CASTLE {
+        san.push_str(if legal_move.to.x() == 6 {
+            "O-O"
+        } else {
+            "O-O-O"
+        });
+    } else {
+        let capture = matches!(legal_move.kind, MoveKind::CAPTURE | MoveKind::ENPASSANT);
+        san.push_str(piece_letter(piece.piece_type));
+        if piece.piece_type == PieceType::PAWN {
+            // A pawn capture is named after the file the pawn comes from
+            if capture {
+                san.push_str(&legal_move.from.to_string()[..1]);
+            }
+        } else {
+            san.push_str(&disambiguation(game, legal_move));
+        }
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&legal_move.to.to_string());
+        if let Some(promotion) = legal_move.promotion {
+            san.push('=');
+            san.push_str(piece_letter(promotion));
+        }
+    }
+    san.push_str(check_suffix(game, legal_move));
+    return san;
+}
+
+// When another piece of the same type can move to the same square, the file of the piece
+// is added, or the rank if they are on the same file, or both if neither is enough
+fn disambiguation(game: &Game, legal_move: Move) -> String {
+    let piece_type = game.board[legal_move.from].piece_type;
+    let others: Vec<Square> = game
+        .legal_moves()
+        .into_iter()
+        .filter(|other| {
+            other.to == legal_move.to
+                && other.from != legal_move.from
+                && game.board[other.from].piece_type == piece_type
+        })
+        .map(|other| other.from)
+        .collect();
+    let from = legal_move.from;
+    let name = from.to_string();
+    if others.is_empty() {
+        return String::new();
+    }
+    if others.iter().all(|other| other.x() != from.x()) {
+        return name[..1].to_string();
+    }
+    if others.iter().all(|other| other.y() != from.y()) {
+        return name[1..].to_string();
+    }
+    return name;
+}
+
+// Finds the legal move written in standard algebraic notation, like Nf3, exd6, O-O-O or e8=Q+.
+// Check and mate signs and annotations like ! or ? are allowed but not checked,
+// castling can also be written with zeros and the = before the promotion piece can be left out
+pub fn parse_san(game: &Game, text: &str) -> Result<Move, ChessError> {
+    if game.game_over() {
+        return Err(ChessError::GAMEOVER);
+    }
+    let text = text.trim().trim_end_matches(['+', '#', '!', '?']);
+    let legal_moves = game.legal_moves();
+
+    let castle_file = match text {
+        "O-O" | "0-0" => Some(6),
+        "O-O-O" | "0-0-0" => Some(2),
+        _ => None,
+    };
+    if let Some(file) = castle_file {
+        return legal_moves
+            .into_iter()
+            .find(|legal_move| legal_move.kind == MoveKind::CASTLE && legal_move.to.x() == file)
+            .ok_or(ChessError::INVALIDSAN(SanError::ILLEGAL));
+    }
+
+    let san = read_san(text).ok_or(ChessError::INVALIDSAN(SanError::MALFORMED))?;
+    let candidates: Vec<Move> = legal_moves
+        .into_iter()
+        .filter(|legal_move| {
+            let from = legal_move.from;
+            let capture = matches!(legal_move.kind, MoveKind::CAPTURE | MoveKind::ENPASSANT);
+            // A pawn push is written with only the square, a pawn capture always has the file
+            // it comes from, so e4 can not be read as dxe4
+            let pawn_shape_matches = san.piece_type != PieceType::PAWN
+                || if san.file.is_none() && !san.capture {
+                    !capture && from.x() == san.to.x()
+                } else {
+                    capture && san.file.is_some()
+                };
+            game.board[from].piece_type == san.piece_type
+                && legal_move.to == san.to
+                && san.file.is_none_or(|file| from.x() == file)
+                && san.rank.is_none_or(|rank| from.rank() == rank)
+                && (!san.capture || capture)
+                && pawn_shape_matches
+                && legal_move.kind != MoveKind::CASTLE
+        })
+        .collect();
+    let matching: Vec<Move> = candidates
+        .iter()
+        .copied()
+        .filter(|legal_move| legal_move.promotion == san.promotion)
+        .collect();
+
+    match matching.len() {
+        1 => return Ok(matching[0]),
+        0 if san.promotion.is_none() && !candidates.is_empty() => {
+            return Err(ChessError::PROMOTIONPENDING);
+        }
+        0 => return Err(ChessError::INVALIDSAN(SanError::ILLEGAL)),
+        _ => return Err(ChessError::INVALIDSAN(SanError::AMBIGUOUS)),
+    }
+}
+
+// Reads a move in the long algebraic notation of the UCI protocol, the squares the piece moves
+// between and a lowercase promotion piece, like e2e4 or e7e8q. Castling is the king move, like e1g1.
+// The move has to be legal, the errors are the same as for Game::make_move
+pub fn parse_uci(game: &Game, text: &str) -> Result<Move, ChessError> {
+    let text = text.trim();
+    if !text.is_ascii() || !(4..=5).contains(&text.len()) {
+        return Err(ChessError::INVALIDUCI);
+    }
+    let from: Square = text[0..2].parse().map_err(|_| ChessError::INVALIDUCI)?;
+    let to: Square = text[2..4].parse().map_err(|_| ChessError::INVALIDUCI)?;
+    let mut piece_move = Move::new(from, to, MoveKind::QUIET);
+    match &text[4..] {
+        "" => {}
+        "q" => piece_move = piece_move.with_promotion(PieceType::QUEEN),
+        "r" => piece_move = piece_move.with_promotion(PieceType::ROOK),
+        "b" => piece_move = piece_move.with_promotion(PieceType::BISHOP),
+        "n" => piece_move = piece_move.with_promotion(PieceType::KNIGHT),
+        _ => return Err(ChessError::INVALIDUCI),
+    }
+    return find_legal_move(game, piece_move);
+}
+
+// The parts of a move in algebraic notation other than castling
+struct San {
+    piece_type: PieceType,
+    // The file and rank of the piece that moves when they are given, rank is 1 to 8
+    file: Option<i32>,
+    rank: Option<i32>,
+    capture: bool,
+    to: Square,
+    promotion: Option<PieceType>,
+}
+
+// Reads [piece][file][rank][x]square[=promotion], None if the text does not have that form
+fn read_san(text: &str) -> Option<San> {
+    let mut chars: Vec<char> = text.chars().collect();
+
+    let promotion = match chars.last() {
+        Some('Q') => Some(PieceType::QUEEN),
+        Some('R') => Some(PieceType::ROOK),
+        Some('B') => Some(PieceType::BISHOP),
+        Some('N') => Some(PieceType::KNIGHT),
+        _ => None,
+    };
+    if promotion.is_some() {
+        chars.pop();
+        if chars.last() == Some(&'=') {
+            chars.pop();
+        }
+    }
+
+    let piece_type = match chars.first() {
+        Some('K') => PieceType::KING,
+        Some('Q') => PieceType::QUEEN,
+        Some('R') => PieceType::ROOK,
+        Some('B') => PieceType::BISHOP,
+        Some('N') => PieceType::KNIGHT,
+        _ => PieceType::PAWN,
+    };
+    if piece_type != PieceType::PAWN {
+        chars.remove(0);
+    }
+    if promotion.is_some() && piece_type != PieceType::PAWN {
+        return None;
+    }
+
+    if chars.len() < 2 {
+        return None;
+    }
+    let to: String = chars.split_off(chars.len() - 2).into_iter().collect();
+    let to: Square = to.parse().ok()?;
+    let capture = chars.last() == Some(&'x');
+    if capture {
+        chars.pop();
+    }
+
+    let mut file = None;
+    let mut rank = None;
+    for character in chars {
+        match character {
+            'a'..='h' if file.is_none() && rank.is_none() => {
+                file = Some(character as i32 - 'a' as i32);
+            }
+            '1'..='8' if rank.is_none() => rank = Some(character as i32 - '0' as i32),
+            _ => return None,
+        }
+    }
+    return Some(San {
+        piece_type,
+        file,
+        rank,
+        capture,
+        to,
+        promotion,
+    });
+}
+
+// + if the move gives check and # if it is check mate
+fn check_suffix(game: &Game, legal_move: Move) -> &'static str {
+    let color = game.board[legal_move.from].color;
+    let mut board = game.board;
+    simulate_piece_move(&mut board, legal_move);
+    if !in_check(board, color.opposite()) {
+        return "";
+    }
+    let mut after = game.clone();
+    play_legal_move(&mut after, legal_move);
+    if after.legal_moves().is_empty() {
+        return "#";
+    }
+    return "+";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::pieces::Color;
+    use crate::error::IllegalMoveReason;
+
+    fn sq(name: &str) -> Square {
+        return name.parse().unwrap();
+    }
+
+    // The notation of the move between the squares, the kind is taken from the legal move
+    fn san(fen: &str, from: &str, to: &str) -> String {
+        let game = Game::from_fen(fen).unwrap();
+        return game
+            .san(Move::new(sq(from), sq(to), MoveKind::QUIET))
+            .unwrap();
+    }
+
+    #[test]
+    fn piece_moves_and_captures() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san(start, "g1", "f3"), "Nf3");
+        assert_eq!(san(start, "e2", "e4"), "e4");
+
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/3PP3/8/PPP2PPP/RNBQKBNR w KQkq - 1 3";
+        assert_eq!(san(fen, "d4", "e5"), "dxe5");
+        assert_eq!(san(fen, "f1", "b5"), "Bb5");
+        assert_eq!(san(fen, "d1", "h5"), "Qh5");
+
+        // En passant is written like a normal pawn capture
+        assert_eq!(san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2", "e5", "d6"), "exd6");
+    }
+
+    #[test]
+    fn castling_promotion_check_and_mate() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san(fen, "e1", "g1"), "O-O");
+        assert_eq!(san(fen, "e1", "c1"), "O-O-O");
+
+        let game = Game::from_fen("k7/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotion = Move::new(sq("e7"), sq("e8"), MoveKind::QUIET);
+        assert_eq!(
+            game.san(promotion.with_promotion(PieceType::QUEEN)),
+            Ok(String::from("e8=Q+"))
+        );
+        assert_eq!(
+            game.san(promotion.with_promotion(PieceType::KNIGHT)),
+            Ok(String::from("e8=N"))
+        );
+        assert_eq!(game.san(promotion), Err(ChessError::PROMOTIONPENDING));
+
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1", "a1", "a8"), "Ra8#");
+    }
+
+    #[test]
+    fn disambiguation_by_file_rank_or_both() {
+        let fen = "4k3/8/8/8/8/8/8/R4RK1 w - - 0 1";
+        assert_eq!(san(fen, "a1", "d1"), "Rad1");
+        assert_eq!(san(fen, "f1", "d1"), "Rfd1");
+
+        let fen = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san(fen, "a1", "a3"), "R1a3");
+        assert_eq!(san(fen, "a5", "a3"), "R5a3");
+        // Only one rook can reach a6
+        assert_eq!(san(fen, "a5", "a6"), "Ra6");
+
+        let fen = "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1";
+        assert_eq!(san(fen, "a1", "b2"), "Qa1b2");
+        assert_eq!(san(fen, "c1", "b2"), "Qcb2");
+        assert_eq!(san(fen, "a3", "b2"), "Q3b2");
+    }
+
+    #[test]
+    fn parse_moves() {
+        let game = Game::new(None);
+        let nf3 = Move::new(sq("g1"), sq("f3"), MoveKind::QUIET);
+        assert_eq!(game.parse_san("Nf3"), Ok(nf3));
+        assert_eq!(game.parse_san(" Ngf3!? "), Ok(nf3));
+        assert_eq!(
+            game.parse_san("e4"),
+            Ok(Move::new(sq("e2"), sq("e4"), MoveKind::DOUBLEPUSH))
+        );
+
+        let game = Game::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        assert_eq!(
+            game.parse_san("exd6"),
+            Ok(Move::new(sq("e5"), sq("d6"), MoveKind::ENPASSANT))
+        );
+
+        let game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+        assert_eq!(
+            game.parse_san("O-O-O"),
+            Ok(Move::new(sq("e8"), sq("c8"), MoveKind::CASTLE))
+        );
+        assert_eq!(game.parse_san("0-0").map(|m| m.to), Ok(sq("g8")));
+
+        let game = Game::from_fen("k7/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotion = Move::new(sq("e7"), sq("e8"), MoveKind::QUIET);
+        assert_eq!(
+            game.parse_san("e8=Q+"),
+            Ok(promotion.with_promotion(PieceType::QUEEN))
+        );
+        assert_eq!(
+            game.parse_san("e8N"),
+            Ok(promotion.with_promotion(PieceType::KNIGHT))
+        );
+        assert_eq!(game.parse_san("e8"), Err(ChessError::PROMOTIONPENDING));
+
+        let game = Game::from_fen("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1").unwrap();
+        assert_eq!(game.parse_san("Qa1b2").map(|m| m.from), Ok(sq("a1")));
+        assert_eq!(game.parse_san("Qcb2").map(|m| m.from), Ok(sq("c1")));
+        assert_eq!(game.parse_san("Q3b2").map(|m| m.from), Ok(sq("a3")));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1").unwrap();
+        let error = |san: &str| game.parse_san(san).unwrap_err();
+        assert_eq!(error("Rd1"), ChessError::INVALIDSAN(SanError::AMBIGUOUS));
+        assert!(game.parse_san("Rad1").is_ok());
+        assert_eq!(error("Rb2"), ChessError::INVALIDSAN(SanError::ILLEGAL));
+        assert_eq!(error("Nf3"), ChessError::INVALIDSAN(SanError::ILLEGAL));
+        assert_eq!(error("O-O"), ChessError::INVALIDSAN(SanError::ILLEGAL));
+        // A capture sign on a move that captures nothing
+        assert_eq!(error("Rxa5"), ChessError::INVALIDSAN(SanError::ILLEGAL));
+        for malformed in ["", "Z4", "e9", "Rd", "Kxx1", "Ne8=Q", "d1R4", "e4e"] {
+            assert_eq!(
+                error(malformed),
+                ChessError::INVALIDSAN(SanError::MALFORMED),
+                "{}",
+                malformed
+            );
+        }
+    }
+
+    // A pawn push can not be read as a capture, and a pawn capture needs its file
+    #[test]
+    fn pawn_pushes_are_not_captures() {
+        let game = Game::from_fen("4k3/8/8/8/4p3/3P4/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            game.parse_san("e4").unwrap_err(),
+            ChessError::INVALIDSAN(SanError::ILLEGAL)
+        );
+        assert_eq!(
+            game.parse_san("xe4").unwrap_err(),
+            ChessError::INVALIDSAN(SanError::ILLEGAL)
+        );
+        assert_eq!(game.parse_san("dxe4").map(|m| m.from), Ok(sq("d3")));
+        let game = Game::from_fen("4k3/8/8/8/4p3/3P1P2/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            game.parse_san("e4").unwrap_err(),
+            ChessError::INVALIDSAN(SanError::ILLEGAL)
+        );
+        assert_eq!(game.parse_san("fxe4").map(|m| m.from), Ok(sq("f3")));
+    }
+
+    // Castling is only written as O-O or O-O-O, never as the king's two square move
+    #[test]
+    fn king_moves_are_not_castling() {
+        let game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        for san in ["Kg1", "Kc1"] {
+            assert_eq!(
+                game.parse_san(san).unwrap_err(),
+                ChessError::INVALIDSAN(SanError::ILLEGAL),
+                "{}",
+                san
+            );
+        }
+        assert_eq!(game.parse_san("O-O").map(|m| m.kind), Ok(MoveKind::CASTLE));
+        assert_eq!(
+            game.parse_san("O-O-O").map(|m| m.kind),
+            Ok(MoveKind::CASTLE)
+        );
+    }
+
+    // Every legal move can be written and read back
+    #[test]
+    fn san_round_trip() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let game = Game::from_fen(fen).unwrap();
+        for legal_move in game.legal_moves() {
+            let san = game.san(legal_move).unwrap();
+            assert_eq!(game.parse_san(&san), Ok(legal_move), "{}", san);
+        }
+    }
+
+    #[test]
+    fn parse_uci_moves() {
+        let game = Game::new(None);
+        assert_eq!(
+            game.parse_uci("e2e4"),
+            Ok(Move::new(sq("e2"), sq("e4"), MoveKind::DOUBLEPUSH))
+        );
+        assert_eq!(
+            game.parse_uci("e2e5"),
+            Err(ChessError::ILLEGALMOVE(IllegalMoveReason::UNREACHABLE))
+        );
+        assert_eq!(
+            game.parse_uci("e7e5"),
+            Err(ChessError::NOTYOURTURN(Color::WHITE))
+        );
+        for malformed in [
+            "", "e2", "e2e", "e2e9", "E2E4", "e2-e4", "e7e8Q", "e7e8k", "e2e4qq",
+        ] {
+            assert_eq!(
+                game.parse_uci(malformed),
+                Err(ChessError::INVALIDUCI),
+                "{}",
+                malformed
+            );
+        }
+
+        let game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(
+            game.parse_uci("e1c1"),
+            Ok(Move::new(sq("e1"), sq("c1"), MoveKind::CASTLE))
+        );
+
+        let game = Game::from_fen("k7/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotion = Move::new(sq("e7"), sq("e8"), MoveKind::QUIET);
+        assert_eq!(
+            game.parse_uci("e7e8n"),
+            Ok(promotion.with_promotion(PieceType::KNIGHT))
+        );
+        assert_eq!(game.parse_uci("e7e8"), Err(ChessError::PROMOTIONPENDING));
+    }
+
+    // Every legal move is written as UCI by Display and can be read back
+    #[test]
+    fn uci_round_trip() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let game = Game::from_fen(fen).unwrap();
+        for legal_move in game.legal_moves() {
+            let uci = legal_move.to_string();
+            assert_eq!(game.parse_uci(&uci), Ok(legal_move), "{}", uci);
+        }
+    }
+
+    #[test]
+    fn play_uci_move_list() {
+        let mut game = Game::new(None);
+        assert_eq!(game.play_uci("f2f3 e7e5 g2g4 d8h4"), Ok(()));
+        assert!(game.check_mate_white);
+        assert_eq!(game.history.len(), 4);
+
+        // The moves before the one that fails stay played
+        let mut game = Game::new(None);
+        assert_eq!(
+            game.play_uci("e2e4 e7e5 e1e3"),
+            Err(ChessError::ILLEGALMOVE(IllegalMoveReason::UNREACHABLE))
+        );
+        assert_eq!(game.history.len(), 2);
+    }
+
+    #[test]
+    fn illegal_moves_have_no_notation() {
+        let game = Game::new(None);
+        assert_eq!(
+            game.san(Move::new(sq("e2"), sq("e5"), MoveKind::QUIET)),
+            Err(ChessError::ILLEGALMOVE(IllegalMoveReason::UNREACHABLE))
+        );
+        let piece_move = Move::new(sq("e7"), sq("e8"), MoveKind::QUIET);
+        assert_eq!(piece_move.to_string(), "e7e8");
+        assert_eq!(
+            piece_move.with_promotion(PieceType::QUEEN).to_string(),
+            "e7e8q"
+        );
+    }
+}
@@ -0,0 +1,809 @@
+use std::{error::Error, fmt};
+
+use crate::board::{
+    parser::FenError,
+    pieces::{play_legal_move, undo_piece_move, Color},
+};
+use crate::error::ChessError;
+use crate::game::{Game, GameResult, Termination};
+use crate::notation::legal_move_to_san;
+
+// The fen string of the standard starting position, games that start anywhere else
+// get the FEN and SetUp tags
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// The Seven Tag Roster, the tags every PGN game has in this order, with the values for unknown
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+// Tags that are written from the game itself and not taken from the given tags
+const GAME_TAGS: [&str; 4] = ["Result", "FEN", "SetUp", "Termination"];
+
+// Lines of the movetext are kept to this length like in the PGN export format
+const LINE_LENGTH: usize = 80;
+
+// Writes the game in PGN. The tags are pairs of name and value, tags of the Seven Tag Roster
+// replace its unknown values and the others are written after it in the given order.
+// Result comes from the game, and a game that did not start from the standard position
+// gets the FEN and SetUp tags. The movetext is in standard algebraic notation
+pub fn write_pgn(game: &Game, tags: &[(&str, &str)]) -> String {
+    // Taking back every move gives the starting position to write the moves from
+    let mut replay = game.clone();
+    while undo_piece_move(&mut replay).is_some() {}
+    replay.undone.clear();
+    let start_fen = replay.to_fen();
+    let result = game.result().to_string();
+
+    let mut pgn = String::new();
+    for (name, unknown) in SEVEN_TAG_ROSTER {
+        let value = match name {
+            "Result" => result.as_str(),
+            _ => tag_value(tags, name).unwrap_or(unknown),
+        };
+        pgn.push_str(&tag_pair(name, value));
+    }
+    if start_fen != START_FEN {
+        pgn.push_str(&tag_pair("SetUp", "1"));
+        pgn.push_str(&tag_pair("FEN", &start_fen));
+    }
+    // How a game that did not end on the board ended, so it can be read back the same way
+    let termination = game
+        .ended
+        .map(|(_, termination)| termination_tag(termination));
+    if let Some(termination) = termination {
+        pgn.push_str(&tag_pair("Termination", termination));
+    }
+    for (name, value) in tags {
+        let roster_tag = SEVEN_TAG_ROSTER.iter().any(|(roster, _)| roster == name);
+        let game_tag =
+            GAME_TAGS.contains(name) && (*name != "Termination" || termination.is_some());
+        if !roster_tag && !game_tag {
+            pgn.push_str(&tag_pair(name, value));
+        }
+    }
+    pgn.push('\n');
+
+    let mut tokens = Vec::new();
+    for (ply, record) in game.history.iter().enumerate() {
+        let white_to_move = replay.turn == Color::WHITE;
+        if white_to_move {
+            tokens.push(format!("{}.", replay.fullmove_number));
+        } else if ply == 0 {
+            // A game that starts with black to move
+            tokens.push(format!("{}...", replay.fullmove_number));
+        }
+        // The game can be over by the rules and still have moves after it, see play_legal_move
+        tokens.push(legal_move_to_san(&replay, record.piece_move));
+        play_legal_move(&mut replay, record.piece_move);
+    }
+    tokens.push(result);
+    pgn.push_str(&wrap(&tokens));
+    pgn.push('\n');
+    return pgn;
+}
+
+// What went wrong while reading a PGN game
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PgnErrorKind {
+    // A tag pair that is not like [Name "value"]
+    INVALIDTAG,
+    // The FEN tag can not be loaded
+    INVALIDFEN(FenError),
+    // A move can not be read or played in the position, holds the error of Game::parse_san
+    INVALIDMOVE(ChessError),
+    // A ) without a variation, or a [ in the middle of the moves
+    UNEXPECTEDTOKEN,
+    // The file ends inside a tag, comment or variation
+    UNTERMINATED,
+    // The result token does not match the Result tag or how the game ended on the board
+    RESULTMISMATCH,
+}
+
+impl fmt::Display for PgnErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnErrorKind::INVALIDTAG => write!(f, "Invalid tag pair"),
+            PgnErrorKind::INVALIDFEN(error) => write!(f, "{}", error),
+            PgnErrorKind::INVALIDMOVE(error) => write!(f, "{}", error),
+            PgnErrorKind::UNEXPECTEDTOKEN => write!(f, "Unexpected token"),
+            PgnErrorKind::UNTERMINATED => write!(f, "Unterminated tag, comment or variation"),
+            PgnErrorKind::RESULTMISMATCH => write!(f, "The result does not match the game"),
+        }
+    }
+}
+
+// Why a PGN file could not be read, and where
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PgnError {
+    // The game in the file, counted from 1
+    pub game: usize,
+    // The half move that was being read, counted from 1, None in the tags
+    pub ply: Option<usize>,
+    // The text where the error is, like the move that could not be played
+    pub token: String,
+    pub kind: PgnErrorKind,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Game {}", self.game)?;
+        if let Some(ply) = self.ply {
+            write!(f, ", ply {}", ply)?;
+        }
+        if !self.token.is_empty() {
+            write!(f, " at '{}'", self.token)?;
+        }
+        return write!(f, ": {}", self.kind);
+    }
+}
+
+impl Error for PgnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            PgnErrorKind::INVALIDFEN(error) => Some(error),
+            PgnErrorKind::INVALIDMOVE(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+// A game read from PGN, with its tags in the order of the file
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub game: Game,
+}
+
+impl PgnGame {
+    // The value of the tag with the name, None if the game does not have it
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        return find_tag(&self.tags, name);
+    }
+}
+
+fn find_tag<'a>(tags: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    return tags
+        .iter()
+        .find(|(tag, _)| tag == name)
+        .map(|(_, value)| value.as_str());
+}
+
+// Reads every game of a PGN file. Each game starts from the FEN tag if it has one and every move
+// of the main line is played through the legality checks. Comments, NAGs like $1 and variations
+// are skipped, the moves in variations are not checked.
+// A game that did not end on the board is ended with the result token by Game::adjudicate,
+// the token has to match the Result tag and a checkmate or draw on the board
+pub fn read_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut reader = Reader {
+        chars: text.chars().collect(),
+        position: 0,
+    };
+    let mut games = Vec::new();
+    loop {
+        let number = games.len() + 1;
+        reader
+            .skip_comments()
+            .map_err(|kind| game_error(number, None, "", kind))?;
+        if reader.peek().is_none() {
+            return Ok(games);
+        }
+        games.push(read_game(&mut reader, number)?);
+    }
+}
+
+fn game_error(game: usize, ply: Option<usize>, token: &str, kind: PgnErrorKind) -> PgnError {
+    return PgnError {
+        game,
+        ply,
+        token: token.to_string(),
+        kind,
+    };
+}
+
+fn read_game(reader: &mut Reader, number: usize) -> Result<PgnGame, PgnError> {
+    let mut tags = Vec::new();
+    while reader.peek() == Some('[') {
+        let tag = reader
+            .read_tag()
+            .map_err(|(token, kind)| game_error(number, None, &token, kind))?;
+        tags.push(tag);
+        reader
+            .skip_comments()
+            .map_err(|kind| game_error(number, None, "", kind))?;
+    }
+    let mut game = match find_tag(&tags, "FEN") {
+        Some(fen) => Game::from_fen(fen).map_err(|error| {
+            let kind = match error {
+                ChessError::INVALIDFEN(error) => PgnErrorKind::INVALIDFEN(error),
+                error => PgnErrorKind::INVALIDMOVE(error),
+            };
+            game_error(number, None, fen, kind)
+        })?,
+        None => Game::new(None),
+    };
+
+    // How deep in variations the reader is, 0 for the main line
+    let mut depth = 0;
+    let mut ply = 0;
+    let mut result = None;
+    while result.is_none() {
+        let error = |token: &str, kind| game_error(number, Some(ply + 1), token, kind);
+        reader.skip_comments().map_err(|kind| error("", kind))?;
+        match reader.peek() {
+            None if depth > 0 => return Err(error("(", PgnErrorKind::UNTERMINATED)),
+            // A game without a result token ends at the end of the file or the next tags
+            None => break,
+            Some('[') if depth == 0 => break,
+            Some('(') => {
+                reader.position += 1;
+                depth += 1;
+            }
+            Some(')') if depth > 0 => {
+                reader.position += 1;
+                depth -= 1;
+            }
+            Some(_) => {
+                let token = reader.read_token();
+                if token.is_empty() {
+                    let character = reader.chars[reader.position].to_string();
+                    return Err(error(&character, PgnErrorKind::UNEXPECTEDTOKEN));
+                }
+                if depth > 0 || token.starts_with('$') {
+                    continue;
+                }
+                if let Some(token_result) = read_result(&token) {
+                    result = Some((token_result, token));
+                    continue;
+                }
+                // e.p. after an en passant capture is allowed, written apart or right after it
+                let san = without_move_number(&token);
+                let san = san.strip_suffix("e.p.").unwrap_or(san);
+                if san.trim_matches(['!', '?']).is_empty() {
+                    continue;
+                }
+                let legal_move = game
+                    .parse_san(san)
+                    .map_err(|chess_error| error(san, PgnErrorKind::INVALIDMOVE(chess_error)))?;
+                play_legal_move(&mut game, legal_move);
+                ply += 1;
+            }
+        }
+    }
+
+    let (result, token) = match result {
+        Some(result) => result,
+        None => {
+            let token = find_tag(&tags, "Result").unwrap_or("*").to_string();
+            (read_result(&token).unwrap_or(GameResult::ONGOING), token)
+        }
+    };
+    let mismatch = || game_error(number, Some(ply + 1), &token, PgnErrorKind::RESULTMISMATCH);
+    if find_tag(&tags, "Result").is_some_and(|tag| read_result(tag) != Some(result)) {
+        return Err(mismatch());
+    }
+    if game.game_over() {
+        if result != game.result() {
+            return Err(mismatch());
+        }
+    } else if result != GameResult::ONGOING {
+        end_game(&mut game, result, find_tag(&tags, "Termination")).map_err(|_| mismatch())?;
+        if game.result() != result {
+            return Err(mismatch());
+        }
+    }
+    return Ok(PgnGame { tags, game });
+}
+
+// Ends a game that did not end on the board the way the Termination tag says.
+// Without the tag, or with Normal, a decisive game was resigned and a draw was claimed
+// if a draw can be claimed and agreed otherwise. Other reasons are ended by adjudication
+fn end_game(
+    game: &mut Game,
+    result: GameResult,
+    termination: Option<&str>,
+) -> Result<(), ChessError> {
+    let loser = match result {
+        GameResult::WHITEWINS => Color::BLACK,
+        GameResult::BLACKWINS => Color::WHITE,
+        GameResult::DRAW | GameResult::ONGOING => Color::EMPTY,
+    };
+    let termination = termination.map(|termination| termination.to_lowercase());
+    match termination.as_deref() {
+        Some("resignation") => return game.resign(loser),
+        Some("agreement") => return game.agree_draw(),
+        Some("repetition") | Some("fifty move rule") => return game.claim_draw(),
+        Some("timeout") | Some("time forfeit") if loser != Color::EMPTY => {
+            return game.timeout(loser);
+        }
+        // A draw on time, the side whose time ran out is the one the other side can not mate
+        Some("timeout") | Some("time forfeit") => {
+            let mut white_timeout = game.clone();
+            if white_timeout.timeout(Color::WHITE).is_ok() && white_timeout.result() == result {
+                *game = white_timeout;
+                return Ok(());
+            }
+            return game.timeout(Color::BLACK);
+        }
+        // The board would have ended the game already
+        Some("checkmate") | Some("stalemate") | Some("insufficient material") => {
+            return Err(ChessError::NORESULT);
+        }
+        None | Some("normal") if result == GameResult::DRAW => {
+            return game.claim_draw().or_else(|_| game.agree_draw());
+        }
+        None | Some("normal") => return game.resign(loser),
+        Some(_) => return game.adjudicate(result),
+    }
+}
+
+// The result tokens at the end of the movetext
+fn read_result(token: &str) -> Option<GameResult> {
+    match token {
+        "1-0" => Some(GameResult::WHITEWINS),
+        "0-1" => Some(GameResult::BLACKWINS),
+        "1/2-1/2" => Some(GameResult::DRAW),
+        "*" => Some(GameResult::ONGOING),
+        _ => None,
+    }
+}
+
+// Move numbers like 12. or 12... can be written right before the move, like 12.Nf3
+fn without_move_number(token: &str) -> &str {
+    let without_digits = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    if without_digits.len() < token.len() && without_digits.starts_with('.') {
+        return without_digits.trim_start_matches('.');
+    }
+    return token;
+}
+
+// Goes through the text of a PGN file one character at a time
+struct Reader {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Reader {
+    fn peek(&self) -> Option<char> {
+        return self.chars.get(self.position).copied();
+    }
+
+    // Skips whitespace, {comments}, ; comments to the end of the line
+    // and lines starting with % which are for other programs
+    fn skip_comments(&mut self) -> Result<(), PgnErrorKind> {
+        while let Some(character) = self.peek() {
+            let line_start = self.position == 0 || self.chars[self.position - 1] == '\n';
+            if character.is_whitespace() {
+                self.position += 1;
+            } else if character == '{' {
+                let end = self.chars[self.position..].iter().position(|&c| c == '}');
+                let Some(end) = end else {
+                    return Err(PgnErrorKind::UNTERMINATED);
+                };
+                self.position += end + 1;
+            } else if character == ';' || (character == '%' && line_start) {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.position += 1;
+                }
+            } else {
+                return Ok(());
+            }
+        }
+        return Ok(());
+    }
+
+    // Everything up to the next whitespace or character that starts or ends something else
+    fn read_token(&mut self) -> String {
+        let mut token = String::new();
+        while let Some(character) = self.peek() {
+            if character.is_whitespace() || "{}();[]".contains(character) {
+                break;
+            }
+            // A NAG ends where the next annotation starts, like $1$3
+            if character == '$' && !token.is_empty() {
+                break;
+            }
+            token.push(character);
+            self.position += 1;
+        }
+        return token;
+    }
+
+    // [Name "value"], a \ in the value escapes the next character
+    // Returns the text read so far with the error
+    fn read_tag(&mut self) -> Result<(String, String), (String, PgnErrorKind)> {
+        let start = self.position;
+        let error = |reader: &Reader, kind| {
+            let text: String = reader.chars[start..reader.position].iter().collect();
+            return (text, kind);
+        };
+        // Over the [
+        self.position += 1;
+        self.skip_spaces();
+        let mut name = String::new();
+        while let Some(character) = self.peek() {
+            if !(character.is_ascii_alphanumeric() || character == '_') {
+                break;
+            }
+            name.push(character);
+            self.position += 1;
+        }
+        self.skip_spaces();
+        if name.is_empty() || self.peek() != Some('"') {
+            return Err(error(self, PgnErrorKind::INVALIDTAG));
+        }
+        self.position += 1;
+
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(error(self, PgnErrorKind::UNTERMINATED)),
+                Some('"') => break,
+                Some('\\') => {
+                    self.position += 1;
+                    let Some(escaped) = self.peek() else {
+                        return Err(error(self, PgnErrorKind::UNTERMINATED));
+                    };
+                    value.push(escaped);
+                }
+                Some(character) => value.push(character),
+            }
+            self.position += 1;
+        }
+        self.position += 1;
+        self.skip_spaces();
+        if self.peek() != Some(']') {
+            return Err(error(self, PgnErrorKind::INVALIDTAG));
+        }
+        self.position += 1;
+        return Ok((name, value));
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(|c| c == ' ' || c == '\t') {
+            self.position += 1;
+        }
+    }
+}
+
+// The value of the last tag with the name, so a tag given twice uses the later value
+fn tag_value<'a>(tags: &[(&str, &'a str)], name: &str) -> Option<&'a str> {
+    return tags
+        .iter()
+        .rev()
+        .find(|(tag, _)| *tag == name)
+        .map(|(_, value)| *value);
+}
+
+// The standard value of the Termination tag. Resignation, agreement and draw claims are all
+// normal, the reader tells them apart by the result and whether a draw can be claimed
+fn termination_tag(termination: Termination) -> &'static str {
+    match termination {
+        Termination::TIMEOUT => return "time forfeit",
+        Termination::ADJUDICATION => return "adjudication",
+        _ => return "normal",
+    }
+}
+
+// [Name "value"] with quotes and backslashes in the value escaped
+fn tag_pair(name: &str, value: &str) -> String {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    return format!("[{} \"{}\"]\n", name, value);
+}
+
+// The tokens separated by spaces, with a new line before a token that does not fit anymore
+fn wrap(tokens: &[String]) -> String {
+    let mut text = String::new();
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > LINE_LENGTH {
+            text.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            text.push(' ');
+            line_length += 1;
+        }
+        text.push_str(token);
+        line_length += token.len();
+    }
+    return text;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{pieces::MoveKind, square::Square};
+    use crate::notation::SanError;
+
+    #[test]
+    fn seven_tag_roster_and_movetext() {
+        let mut game = Game::new(None);
+        game.play_uci("f2f3 e7e5 g2g4 d8h4").unwrap();
+        let pgn = game.to_pgn(&[
+            ("White", "Fool"),
+            ("Event", "Casual game"),
+            ("Annotator", "Nobody"),
+            ("Result", "1-0"),
+        ]);
+        assert_eq!(
+            pgn,
+            "[Event \"Casual game\"]\n\
+             [Site \"?\"]\n\
+             [Date \"????.??.??\"]\n\
+             [Round \"?\"]\n\
+             [White \"Fool\"]\n\
+             [Black \"?\"]\n\
+             [Result \"0-1\"]\n\
+             [Annotator \"Nobody\"]\n\
+             \n\
+             1. f3 e5 2. g4 Qh4# 0-1\n"
+        );
+    }
+
+    #[test]
+    fn game_from_a_position() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 3 40").unwrap();
+        game.play_uci("e8d7 e2e4 d7c6").unwrap();
+        game.resign(Color::WHITE).unwrap();
+        let pgn = game.to_pgn(&[]);
+        assert!(pgn.contains(
+            "[Result \"0-1\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 3 40\"]\n[Termination \"normal\"]\n\n"
+        ));
+        assert!(pgn.ends_with("\n40... Kd7 41. e4 Kc6 0-1\n"));
+        assert_eq!(game.result(), GameResult::BLACKWINS);
+
+        // Writing the game leaves it as it was
+        assert_eq!(game.history.len(), 3);
+        assert_eq!(game.to_fen(), "8/8/2k5/8/4P3/8/8/4K3 w - - 1 42");
+    }
+
+    #[test]
+    fn moves_after_the_game_is_over() {
+        // Two kings are a draw by insufficient material, play_legal_move plays on anyway
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let d1: Square = "d1".parse().unwrap();
+        let king_move = game.legal_moves().into_iter().find(|m| m.to == d1).unwrap();
+        play_legal_move(&mut game, king_move);
+        assert!(game.game_over());
+        let pgn = game.to_pgn(&[]);
+        assert!(pgn.ends_with("[FEN \"4k3/8/8/8/8/8/8/4K3 w - - 0 1\"]\n\n1. Kd1 1/2-1/2\n"));
+    }
+
+    #[test]
+    fn ongoing_game_and_escaped_tags() {
+        let game = Game::new(None);
+        let pgn = game.to_pgn(&[("Event", "The \"big\" one \\ final")]);
+        assert!(pgn.starts_with("[Event \"The \\\"big\\\" one \\\\ final\"]\n"));
+        assert!(!pgn.contains("FEN"));
+        assert!(pgn.ends_with("[Result \"*\"]\n\n*\n"));
+    }
+
+    #[test]
+    fn long_movetext_is_wrapped() {
+        let mut game = Game::new(None);
+        game.play_uci(
+            "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5a4 g8f6 e1g1 f8e7 \
+             f1e1 b7b5 a4b3 d7d6 c2c3 e8g8 h2h3 c6b8 d2d4 b8d7",
+        )
+        .unwrap();
+        let pgn = game.to_pgn(&[]);
+        let movetext = pgn.split("\n\n").nth(1).unwrap();
+        assert!(movetext.lines().count() > 1);
+        assert!(movetext.lines().all(|line| line.len() <= LINE_LENGTH));
+        assert!(movetext.starts_with("1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7"));
+        assert!(movetext.ends_with("10. d4 Nbd7 *\n"));
+    }
+
+    const TWO_GAMES: &str = r#"[Event "Casual game"]
+[Site "?"]
+[White "Fool"]
+[Black "Scholar"]
+[Result "0-1"]
+
+1. f3 {A weak move} e5 2. g4?? (2. e4 Nc6 (2... Bc5) 3. Nf3) 2... Qh4# 0-1
+
+; The second game
+[Event "Opening"]
+[Annotator "Someone \"quoted\""]
+[Result "1/2-1/2"]
+
+1.e4 $1 e5 2.Nf3 $14 Nc6 3.Bb5 a6
+% a line for another program
+4. Ba4 Nf6 5. O-O {Castles} 1/2-1/2
+"#;
+
+    #[test]
+    fn read_several_games() {
+        let games = read_pgn(TWO_GAMES).unwrap();
+        assert_eq!(games.len(), 2);
+
+        let fools_mate = &games[0];
+        assert_eq!(fools_mate.tag("White"), Some("Fool"));
+        assert_eq!(fools_mate.tag("Round"), None);
+        assert_eq!(fools_mate.game.history.len(), 4);
+        assert!(fools_mate.game.check_mate_white);
+        assert_eq!(fools_mate.game.result(), GameResult::BLACKWINS);
+
+        let opening = &games[1];
+        assert_eq!(opening.tag("Annotator"), Some("Someone \"quoted\""));
+        assert_eq!(opening.game.history.len(), 9);
+        assert_eq!(
+            opening.game.to_fen(),
+            "r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 3 5"
+        );
+        // The game did not end on the board and no draw can be claimed, so it was agreed
+        assert_eq!(opening.game.result(), GameResult::DRAW);
+        assert_eq!(opening.game.termination(), Some(Termination::AGREEMENT));
+    }
+
+    #[test]
+    fn read_game_from_a_position() {
+        let pgn = "[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 3 40\"]\n\n40... Kd7 41. e4 *";
+        let games = read_pgn(pgn).unwrap();
+        assert_eq!(games[0].game.to_fen(), "8/3k4/8/8/4P3/8/8/4K3 b - e3 0 41");
+        assert_eq!(games[0].game.result(), GameResult::ONGOING);
+        assert!(read_pgn("").unwrap().is_empty());
+    }
+
+    // Writing the games and reading them back gives the same games
+    #[test]
+    fn write_and_read_back() {
+        for game in read_pgn(TWO_GAMES).unwrap() {
+            let pgn = game.game.to_pgn(&[("Event", "Again")]);
+            let read = read_pgn(&pgn).unwrap();
+            assert_eq!(read.len(), 1);
+            assert_eq!(read[0].tag("Event"), Some("Again"));
+            assert_eq!(read[0].game.to_fen(), game.game.to_fen());
+            assert_eq!(read[0].game.result(), game.game.result());
+            let moves = |game: &Game| {
+                game.history
+                    .iter()
+                    .map(|r| r.piece_move)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(moves(&read[0].game), moves(&game.game));
+        }
+    }
+
+    // The Termination tag is written and read back, so the game ends the same way
+    #[test]
+    fn games_end_the_same_way() {
+        let mut resigned = Game::new(None);
+        resigned.play_uci("e2e4 e7e5").unwrap();
+        resigned.resign(Color::BLACK).unwrap();
+
+        let mut agreed = Game::new(None);
+        agreed.play_uci("d2d4 d7d5").unwrap();
+        agreed.agree_draw().unwrap();
+
+        let mut claimed = Game::new(None);
+        claimed
+            .play_uci("g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8")
+            .unwrap();
+        claimed.claim_draw().unwrap();
+
+        let mut timeout = Game::from_fen("4k3/8/8/8/8/8/4P3/4K2R w K - 0 1").unwrap();
+        timeout.timeout(Color::BLACK).unwrap();
+        let mut drawn_on_time = Game::from_fen("4k3/p7/8/8/8/8/8/4KN2 b - - 0 1").unwrap();
+        drawn_on_time.timeout(Color::BLACK).unwrap();
+
+        let mut adjudicated = Game::new(None);
+        adjudicated.adjudicate(GameResult::WHITEWINS).unwrap();
+
+        for game in [
+            resigned,
+            agreed,
+            claimed,
+            timeout,
+            drawn_on_time,
+            adjudicated,
+        ] {
+            let pgn = game.to_pgn(&[("Termination", "ignored")]);
+            let read = read_pgn(&pgn).unwrap();
+            assert_eq!(read[0].game.ended, game.ended, "{}", pgn);
+            assert_eq!(read[0].game.to_fen(), game.to_fen());
+        }
+
+        // Without a Termination tag a decisive game was resigned and a draw claimed if possible
+        let read = read_pgn("1. e4 e5 1-0").unwrap();
+        assert_eq!(read[0].game.termination(), Some(Termination::RESIGNATION));
+        let read = read_pgn("1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 Ng8 1/2-1/2").unwrap();
+        assert_eq!(read[0].game.termination(), Some(Termination::REPETITION));
+        let read = read_pgn("[Termination \"abandoned\"]\n1. e4 0-1").unwrap();
+        assert_eq!(read[0].game.termination(), Some(Termination::ADJUDICATION));
+        // A timeout is written with the standard value and read back as a timeout
+        let mut lost_on_time = Game::new(None);
+        lost_on_time.play_uci("e2e4").unwrap();
+        lost_on_time.timeout(Color::BLACK).unwrap();
+        let pgn = lost_on_time.to_pgn(&[]);
+        assert!(pgn.contains("[Termination \"time forfeit\"]"), "{}", pgn);
+        let read = read_pgn(&pgn).unwrap();
+        assert_eq!(read[0].tag("Termination"), Some("time forfeit"));
+        assert_eq!(read[0].game.termination(), Some(Termination::TIMEOUT));
+        assert_eq!(read[0].game.result(), GameResult::WHITEWINS);
+        // A game that is not ended gets no Termination tag, so one that is given is kept
+        let pgn = Game::new(None).to_pgn(&[("Termination", "unterminated")]);
+        assert!(pgn.contains("[Termination \"unterminated\"]"));
+    }
+
+    #[test]
+    fn en_passant_suffix() {
+        for pgn in [
+            "1. e4 Nf6 2. e5 d5 3. exd6 e.p. *",
+            "1. e4 Nf6 2. e5 d5 3. exd6e.p. *",
+        ] {
+            let read = read_pgn(pgn).unwrap();
+            assert_eq!(read[0].game.history.len(), 5, "{}", pgn);
+            let last_move = read[0].game.history.last().unwrap().piece_move;
+            assert_eq!(last_move.kind, MoveKind::ENPASSANT);
+        }
+    }
+
+    fn error(pgn: &str) -> PgnError {
+        return read_pgn(pgn).err().unwrap();
+    }
+
+    #[test]
+    fn errors_point_at_the_game_and_ply() {
+        let pgn = "1. e4 e5 *\n\n[Event \"Second\"]\n\n1. d4 d5 2. Nxd5 *";
+        let illegal = error(pgn);
+        assert_eq!(illegal.game, 2);
+        assert_eq!(illegal.ply, Some(3));
+        assert_eq!(illegal.token, "Nxd5");
+        assert_eq!(
+            illegal.kind,
+            PgnErrorKind::INVALIDMOVE(ChessError::INVALIDSAN(SanError::ILLEGAL))
+        );
+        assert_eq!(
+            illegal.to_string(),
+            "Game 2, ply 3 at 'Nxd5': No legal move matches the notation"
+        );
+
+        // Moves after the game ended on the board
+        let after_mate = error("1. f3 e5 2. g4 Qh4# 3. a3 0-1");
+        assert_eq!(after_mate.ply, Some(5));
+        assert_eq!(
+            after_mate.kind,
+            PgnErrorKind::INVALIDMOVE(ChessError::GAMEOVER)
+        );
+
+        assert_eq!(error("[Event Casual]\n*").kind, PgnErrorKind::INVALIDTAG);
+        assert_eq!(error("[Event \"Casual\"\n*").kind, PgnErrorKind::INVALIDTAG);
+        assert_eq!(error("[Event \"Casual").kind, PgnErrorKind::UNTERMINATED);
+        assert_eq!(
+            error("1. e4 {never closed").kind,
+            PgnErrorKind::UNTERMINATED
+        );
+        assert_eq!(error("1. e4 (1. d4 *").kind, PgnErrorKind::UNTERMINATED);
+        assert_eq!(error("1. e4 ) e5 *").kind, PgnErrorKind::UNEXPECTEDTOKEN);
+        assert!(matches!(
+            error("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n*").kind,
+            PgnErrorKind::INVALIDFEN(_)
+        ));
+
+        // The result token has to agree with the tag and with the board
+        let mismatch = error("[Result \"1-0\"]\n\n1. e4 0-1");
+        assert_eq!(
+            (mismatch.ply, mismatch.kind),
+            (Some(2), PgnErrorKind::RESULTMISMATCH)
+        );
+        assert_eq!(
+            error("1. f3 e5 2. g4 Qh4# 1-0").kind,
+            PgnErrorKind::RESULTMISMATCH
+        );
+        // A game that ended on the board has a result
+        assert_eq!(
+            error("1. f3 e5 2. g4 Qh4# *").kind,
+            PgnErrorKind::RESULTMISMATCH
+        );
+        assert_eq!(
+            error("[Termination \"Checkmate\"]\n1. e4 1-0").kind,
+            PgnErrorKind::RESULTMISMATCH
+        );
+    }
+}